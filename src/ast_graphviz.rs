@@ -0,0 +1,318 @@
+use std::convert::Infallible;
+
+use crate::{
+    common::LoxType,
+    expr::{self, Expr},
+    stmt::{self, Stmt},
+};
+
+// renders a parsed program as a graphviz DOT digraph, one node per Expr/Stmt
+#[derive(Default)]
+pub struct AstGraphviz {
+    nodes: Vec<String>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl AstGraphviz {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn render(&mut self, statements: &[Stmt]) -> String {
+        self.nodes.clear();
+        self.edges.clear();
+
+        let program = self.node("Program".to_string());
+        for stmt in statements {
+            let child = self.stmt(stmt);
+            self.edge(program, child);
+        }
+
+        let mut dot = String::from("digraph ast {\n");
+        for (id, label) in self.nodes.iter().enumerate() {
+            dot.push_str(&format!("  node{} [label=\"{}\"];\n", id, escape(label)));
+        }
+        for (from, to) in self.edges.iter() {
+            dot.push_str(&format!("  node{} -> node{};\n", from, to));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn node(&mut self, label: String) -> usize {
+        self.nodes.push(label);
+        self.nodes.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, to: usize) {
+        self.edges.push((from, to));
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> usize {
+        match stmt::Visitor::visit_stmt(self, stmt) {
+            Ok(id) => id,
+            Err(never) => match never {},
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> usize {
+        match expr::Visitor::visit_expr(self, expr) {
+            Ok(id) => id,
+            Err(never) => match never {},
+        }
+    }
+
+    fn with_children(&mut self, label: String, exprs: &[&Expr], stmts: &[&Stmt]) -> usize {
+        let id = self.node(label);
+        for expr in exprs {
+            let child = self.expr(expr);
+            self.edge(id, child);
+        }
+        for stmt in stmts {
+            let child = self.stmt(stmt);
+            self.edge(id, child);
+        }
+        id
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl expr::Visitor<usize, Infallible> for AstGraphviz {
+    fn visit_expr(&mut self, expr: &Expr) -> Result<usize, Infallible> {
+        Ok(match expr {
+            Expr::Assign { name, value } => {
+                self.with_children(format!("Assign {}", name.raw), &[value], &[])
+            }
+            Expr::Binary {
+                left,
+                right,
+                operator,
+            } => self.with_children(format!("Binary {}", operator.raw), &[left, right], &[]),
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                let mut children: Vec<&Expr> = vec![callee];
+                children.extend(arguments.iter());
+                self.with_children("Call".to_string(), &children, &[])
+            }
+            Expr::Get { object, name } => {
+                self.with_children(format!("Get {}", name.raw), &[object], &[])
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+                operator,
+            } => {
+                let label = match operator {
+                    Some(operator) => format!("Set {} {}=", name.raw, operator.raw),
+                    None => format!("Set {}", name.raw),
+                };
+                self.with_children(label, &[object, value], &[])
+            }
+            Expr::Grouping { expression } => {
+                self.with_children("Grouping".to_string(), &[expression], &[])
+            }
+            Expr::Literal { value, .. } => match value {
+                LoxType::Strang(s) => self.node(format!("Literal \"{}\"", s)),
+                _ => self.node(format!("Literal {}", value)),
+            },
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => self.with_children(format!("Logical {}", operator.raw), &[left, right], &[]),
+            Expr::Unary { operator, right } => {
+                self.with_children(format!("Unary {}", operator.raw), &[right], &[])
+            }
+            Expr::Variable { name } => self.node(format!("Variable {}", name.raw)),
+            Expr::This { .. } => self.node("This".to_string()),
+            Expr::Index { object, index, .. } => {
+                self.with_children("Index".to_string(), &[object, index], &[])
+            }
+            Expr::IndexSet {
+                object,
+                index,
+                value,
+                operator,
+                ..
+            } => {
+                let label = match operator {
+                    Some(operator) => format!("IndexSet {}=", operator.raw),
+                    None => "IndexSet".to_string(),
+                };
+                self.with_children(label, &[object, index, value], &[])
+            }
+            Expr::Slice {
+                object, start, end, ..
+            } => {
+                let label = slice_label("Slice", start, end);
+                let children: Vec<&Expr> = [Some(object), start.as_ref(), end.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .map(|e| e.as_ref())
+                    .collect();
+                self.with_children(label, &children, &[])
+            }
+            Expr::SliceSet {
+                object,
+                start,
+                end,
+                value,
+                ..
+            } => {
+                let label = slice_label("SliceSet", start, end);
+                let children: Vec<&Expr> = [Some(object), start.as_ref(), end.as_ref(), Some(value)]
+                    .into_iter()
+                    .flatten()
+                    .map(|e| e.as_ref())
+                    .collect();
+                self.with_children(label, &children, &[])
+            }
+            Expr::MapLiteral { entries, .. } => {
+                let children: Vec<&Expr> = entries.iter().flat_map(|(k, v)| [k, v]).collect();
+                self.with_children("Map".to_string(), &children, &[])
+            }
+            Expr::Lambda {
+                parameters, body, ..
+            } => {
+                let parameters: Vec<&str> = parameters.iter().map(|p| p.raw.as_str()).collect();
+                let children: Vec<&Stmt> = body.iter().collect();
+                self.with_children(format!("Lambda({})", parameters.join(", ")), &[], &children)
+            }
+            Expr::ArrayLiteral { elements, .. } => {
+                let children: Vec<&Expr> = elements.iter().collect();
+                self.with_children("List".to_string(), &children, &[])
+            }
+            Expr::Spread { expression, .. } => self.with_children("Spread".to_string(), &[expression], &[]),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => self.with_children(
+                "Ternary".to_string(),
+                &[condition, then_branch, else_branch],
+                &[],
+            ),
+            Expr::Super { method, .. } => self.node(format!("Super {}", method.raw)),
+        })
+    }
+}
+
+// marks which bounds a slice was given, as they can be left out, e.g. Slice [start:]
+fn slice_label(name: &str, start: &Option<Box<Expr>>, end: &Option<Box<Expr>>) -> String {
+    let start = if start.is_some() { "start" } else { "" };
+    let end = if end.is_some() { "end" } else { "" };
+    format!("{} [{}:{}]", name, start, end)
+}
+
+impl stmt::Visitor<usize, Infallible> for AstGraphviz {
+    fn visit_stmt(&mut self, stmt: &Stmt) -> Result<usize, Infallible> {
+        Ok(match stmt {
+            Stmt::Block { statements, .. } => {
+                let children: Vec<&Stmt> = statements.iter().collect();
+                self.with_children("Block".to_string(), &[], &children)
+            }
+            Stmt::Expression { expression } => {
+                self.with_children("Expression".to_string(), &[expression], &[])
+            }
+            // the targets come first, then the values in the same order
+            Stmt::MultiAssign { targets, values, .. } => {
+                let children: Vec<&Expr> = targets.iter().chain(values).collect();
+                self.with_children("MultiAssign".to_string(), &children, &[])
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let mut children: Vec<&Stmt> = vec![then_branch];
+                if let Some(else_branch) = else_branch {
+                    children.push(else_branch);
+                }
+                self.with_children("If".to_string(), &[condition], &children)
+            }
+            Stmt::ForEach {
+                variable,
+                iterable,
+                body,
+                ..
+            } => self.with_children(format!("ForEach {}", variable.raw), &[iterable], &[body]),
+            Stmt::While {
+                condition,
+                then_branch,
+                increment,
+                finally_branch,
+                ..
+            } => {
+                let mut exprs = vec![condition];
+                if let Some(increment) = increment {
+                    exprs.push(increment);
+                }
+                let mut children: Vec<&Stmt> = vec![then_branch];
+                if let Some(finally_branch) = finally_branch {
+                    children.push(finally_branch);
+                }
+                self.with_children("While".to_string(), &exprs, &children)
+            }
+            Stmt::Print { expression, .. } => {
+                self.with_children("Print".to_string(), &[expression], &[])
+            }
+            Stmt::Assert {
+                condition, message, ..
+            } => {
+                let mut children = vec![condition];
+                children.extend(message);
+                self.with_children("Assert".to_string(), &children, &[])
+            }
+            Stmt::Defer { expression, .. } => {
+                self.with_children("Defer".to_string(), &[expression], &[])
+            }
+            Stmt::Break { .. } => self.node("Break".to_string()),
+            Stmt::Continue { .. } => self.node("Continue".to_string()),
+            Stmt::Return { return_value, .. } => match return_value {
+                Some(value) => self.with_children("Return".to_string(), &[value], &[]),
+                None => self.node("Return".to_string()),
+            },
+            Stmt::Var {
+                name,
+                initializer,
+                is_const,
+            } => {
+                let label = format!("{} {}", if *is_const { "Val" } else { "Var" }, name.raw);
+                match initializer {
+                    Some(init) => self.with_children(label, &[init], &[]),
+                    None => self.node(label),
+                }
+            }
+            Stmt::Function {
+                name,
+                parameters,
+                body,
+                pure,
+            } => {
+                let parameters: Vec<&str> = parameters.iter().map(|p| p.raw.as_str()).collect();
+                let children: Vec<&Stmt> = body.iter().collect();
+                let kind = if *pure { "PureFunction" } else { "Function" };
+                self.with_children(
+                    format!("{} {}({})", kind, name.raw, parameters.join(", ")),
+                    &[],
+                    &children,
+                )
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let superclass: Vec<&Expr> = superclass.iter().collect();
+                let children: Vec<&Stmt> = methods.iter().collect();
+                self.with_children(format!("Class {}", name.raw), &superclass, &children)
+            }
+        })
+    }
+}
@@ -0,0 +1,415 @@
+use std::convert::Infallible;
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    common::{LoxType, Token},
+    expr::{self, Expr},
+    stmt::{self, Stmt},
+};
+
+// renders a parsed program as JSON for editor tooling. Every node is an object with a
+// "type" and its children, tokens keep their line and column so nodes map back to source
+pub struct AstJson;
+
+// the whole program as a JSON array of statements, keys sorted, e.g. for `var x = 1;`
+// [{"const":false,"initializer":{"token":{"column":9,"lexeme":"1","line":1},"type":"Literal","value":1.0},
+//   "name":{"column":5,"lexeme":"x","line":1},"type":"Var"}]
+pub fn ast_json(stmts: &[Stmt]) -> String {
+    let stmts: Vec<Value> = stmts.iter().map(|stmt| AstJson.stmt(stmt)).collect();
+    serde_json::to_string(&stmts).expect("the tree always serializes")
+}
+
+// a SHA-256 of the program's JSON tree with every line and column taken out, as hex. It
+// changes when the parse does, but not for whitespace or comments, so tests can snapshot it
+pub fn ast_fingerprint(stmts: &[Stmt]) -> String {
+    let mut stmts = Value::Array(stmts.iter().map(|stmt| AstJson.stmt(stmt)).collect());
+    strip_positions(&mut stmts);
+    let canonical = serde_json::to_string(&stmts).expect("the tree always serializes");
+    format!("{:x}", Sha256::digest(canonical.as_bytes()))
+}
+
+fn strip_positions(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            object.remove("line");
+            object.remove("column");
+            object.values_mut().for_each(strip_positions);
+        }
+        Value::Array(values) => values.iter_mut().for_each(strip_positions),
+        _ => {}
+    }
+}
+
+// positions are where the token starts, as in --emit-tokens-json
+fn token(token: &Token) -> Value {
+    json!({
+        "lexeme": token.raw,
+        "line": token.start_line,
+        "column": token.start_column,
+    })
+}
+
+fn optional_token(t: &Option<Token>) -> Value {
+    t.as_ref().map_or(Value::Null, token)
+}
+
+fn tokens(tokens: &[Token]) -> Value {
+    Value::Array(tokens.iter().map(token).collect())
+}
+
+fn literal(value: &LoxType) -> Value {
+    match value {
+        LoxType::Number(n) => json!(n),
+        LoxType::Strang(s) => json!(s),
+        LoxType::Bool(b) => json!(b),
+        LoxType::Nil => Value::Null,
+        // the parser only makes the kinds above, anything else is shown as lox prints it
+        other => json!(other.to_string()),
+    }
+}
+
+impl AstJson {
+    fn expr(&mut self, expr: &Expr) -> Value {
+        match expr::Visitor::visit_expr(self, expr) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Value {
+        match stmt::Visitor::visit_stmt(self, stmt) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    fn optional_expr(&mut self, expr: Option<&Expr>) -> Value {
+        expr.map_or(Value::Null, |e| self.expr(e))
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Value {
+        Value::Array(exprs.iter().map(|e| self.expr(e)).collect())
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> Value {
+        Value::Array(stmts.iter().map(|s| self.stmt(s)).collect())
+    }
+}
+
+impl expr::Visitor<Value, Infallible> for AstJson {
+    fn visit_expr(&mut self, expr: &Expr) -> Result<Value, Infallible> {
+        Ok(match expr {
+            Expr::Assign { name, value } => json!({
+                "type": "Assign",
+                "name": token(name),
+                "value": self.expr(value),
+            }),
+            Expr::Binary {
+                left,
+                right,
+                operator,
+            } => json!({
+                "type": "Binary",
+                "operator": token(operator),
+                "left": self.expr(left),
+                "right": self.expr(right),
+            }),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => json!({
+                "type": "Call",
+                "paren": token(paren),
+                "callee": self.expr(callee),
+                "arguments": self.exprs(arguments),
+            }),
+            Expr::Get { object, name } => json!({
+                "type": "Get",
+                "name": token(name),
+                "object": self.expr(object),
+            }),
+            Expr::Set {
+                object,
+                name,
+                value,
+                operator,
+            } => json!({
+                "type": "Set",
+                "name": token(name),
+                "operator": optional_token(operator),
+                "object": self.expr(object),
+                "value": self.expr(value),
+            }),
+            Expr::Grouping { expression } => json!({
+                "type": "Grouping",
+                "expression": self.expr(expression),
+            }),
+            Expr::Literal { value, token: t } => json!({
+                "type": "Literal",
+                "token": token(t),
+                "value": literal(value),
+            }),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => json!({
+                "type": "Logical",
+                "operator": token(operator),
+                "left": self.expr(left),
+                "right": self.expr(right),
+            }),
+            Expr::Unary { operator, right } => json!({
+                "type": "Unary",
+                "operator": token(operator),
+                "right": self.expr(right),
+            }),
+            Expr::Variable { name } => json!({
+                "type": "Variable",
+                "name": token(name),
+            }),
+            Expr::This { keyword } => json!({
+                "type": "This",
+                "keyword": token(keyword),
+            }),
+            Expr::Lambda {
+                keyword,
+                parameters,
+                body,
+            } => json!({
+                "type": "Lambda",
+                "keyword": token(keyword),
+                "parameters": tokens(parameters),
+                "body": self.stmts(body),
+            }),
+            Expr::ArrayLiteral { bracket, elements } => json!({
+                "type": "ArrayLiteral",
+                "bracket": token(bracket),
+                "elements": self.exprs(elements),
+            }),
+            Expr::Spread { ellipsis, expression } => json!({
+                "type": "Spread",
+                "ellipsis": token(ellipsis),
+                "expression": self.expr(expression),
+            }),
+            Expr::MapLiteral { brace, entries } => {
+                let entries: Vec<Value> = entries
+                    .iter()
+                    .map(|(key, value)| json!({"key": self.expr(key), "value": self.expr(value)}))
+                    .collect();
+                json!({
+                    "type": "MapLiteral",
+                    "brace": token(brace),
+                    "entries": entries,
+                })
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => json!({
+                "type": "Index",
+                "bracket": token(bracket),
+                "object": self.expr(object),
+                "index": self.expr(index),
+            }),
+            Expr::IndexSet {
+                object,
+                bracket,
+                index,
+                value,
+                operator,
+            } => json!({
+                "type": "IndexSet",
+                "bracket": token(bracket),
+                "operator": optional_token(operator),
+                "object": self.expr(object),
+                "index": self.expr(index),
+                "value": self.expr(value),
+            }),
+            Expr::Slice {
+                object,
+                bracket,
+                start,
+                end,
+            } => json!({
+                "type": "Slice",
+                "bracket": token(bracket),
+                "object": self.expr(object),
+                "start": self.optional_expr(start.as_deref()),
+                "end": self.optional_expr(end.as_deref()),
+            }),
+            Expr::SliceSet {
+                object,
+                bracket,
+                start,
+                end,
+                value,
+            } => json!({
+                "type": "SliceSet",
+                "bracket": token(bracket),
+                "object": self.expr(object),
+                "start": self.optional_expr(start.as_deref()),
+                "end": self.optional_expr(end.as_deref()),
+                "value": self.expr(value),
+            }),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => json!({
+                "type": "Ternary",
+                "condition": self.expr(condition),
+                "then_branch": self.expr(then_branch),
+                "else_branch": self.expr(else_branch),
+            }),
+            Expr::Super { keyword, method } => json!({
+                "type": "Super",
+                "keyword": token(keyword),
+                "method": token(method),
+            }),
+        })
+    }
+}
+
+impl stmt::Visitor<Value, Infallible> for AstJson {
+    fn visit_stmt(&mut self, stmt: &Stmt) -> Result<Value, Infallible> {
+        Ok(match stmt {
+            Stmt::Block { brace, statements } => json!({
+                "type": "Block",
+                "brace": token(brace),
+                "statements": self.stmts(statements),
+            }),
+            Stmt::Expression { expression } => json!({
+                "type": "Expression",
+                "expression": self.expr(expression),
+            }),
+            Stmt::MultiAssign {
+                equals,
+                targets,
+                values,
+            } => json!({
+                "type": "MultiAssign",
+                "equals": token(equals),
+                "targets": self.exprs(targets),
+                "values": self.exprs(values),
+            }),
+            Stmt::If {
+                keyword,
+                condition,
+                then_branch,
+                else_branch,
+            } => json!({
+                "type": "If",
+                "keyword": token(keyword),
+                "condition": self.expr(condition),
+                "then_branch": self.stmt(then_branch),
+                "else_branch": else_branch.as_ref().map_or(Value::Null, |s| self.stmt(s)),
+            }),
+            Stmt::While {
+                keyword,
+                condition,
+                then_branch,
+                increment,
+                finally_branch,
+                ..
+            } => json!({
+                "type": "While",
+                "keyword": token(keyword),
+                "condition": self.expr(condition),
+                "body": self.stmt(then_branch),
+                "increment": self.optional_expr(increment.as_ref()),
+                "finally": finally_branch.as_ref().map_or(Value::Null, |s| self.stmt(s)),
+            }),
+            Stmt::ForEach {
+                keyword,
+                variable,
+                iterable,
+                body,
+            } => json!({
+                "type": "ForEach",
+                "keyword": token(keyword),
+                "variable": token(variable),
+                "iterable": self.expr(iterable),
+                "body": self.stmt(body),
+            }),
+            Stmt::Print {
+                keyword,
+                expression,
+            } => json!({
+                "type": "Print",
+                "keyword": token(keyword),
+                "expression": self.expr(expression),
+            }),
+            Stmt::Assert {
+                keyword,
+                condition,
+                message,
+            } => json!({
+                "type": "Assert",
+                "keyword": token(keyword),
+                "condition": self.expr(condition),
+                "message": self.optional_expr(message.as_ref()),
+            }),
+            Stmt::Defer {
+                keyword,
+                expression,
+            } => json!({
+                "type": "Defer",
+                "keyword": token(keyword),
+                "expression": self.expr(expression),
+            }),
+            Stmt::Break { token: t } => json!({
+                "type": "Break",
+                "keyword": token(t),
+            }),
+            Stmt::Continue { token: t } => json!({
+                "type": "Continue",
+                "keyword": token(t),
+            }),
+            Stmt::Return {
+                token: t,
+                return_value,
+            } => json!({
+                "type": "Return",
+                "keyword": token(t),
+                "value": self.optional_expr(return_value.as_ref()),
+            }),
+            Stmt::Var {
+                name,
+                initializer,
+                is_const,
+            } => json!({
+                "type": "Var",
+                "name": token(name),
+                "const": is_const,
+                "initializer": self.optional_expr(initializer.as_ref()),
+            }),
+            Stmt::Function {
+                name,
+                parameters,
+                body,
+                pure,
+            } => json!({
+                "type": "Function",
+                "name": token(name),
+                "parameters": tokens(parameters),
+                "pure": pure,
+                "body": self.stmts(body),
+            }),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => json!({
+                "type": "Class",
+                "name": token(name),
+                "superclass": self.optional_expr(superclass.as_ref()),
+                "methods": self.stmts(methods),
+            }),
+        })
+    }
+}
@@ -1,9 +1,35 @@
 use std::string::ParseError;
 
+use crate::common::LoxType;
 use crate::expr::{Expr, Visitor};
+use crate::stmt::{self, Stmt};
 
 pub struct AstPrinter {}
 
+impl AstPrinter {
+    // one line per top level statement
+    pub fn print_program(&mut self, stmts: &[Stmt]) -> String {
+        stmts
+            .iter()
+            .map(|stmt| match stmt::Visitor::visit_stmt(self, stmt) {
+                Ok(rendered) => rendered,
+                Err(never) => match never {},
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // each statement rendered and space separated, with a leading space if there are any
+    fn stmts(&mut self, stmts: &[Stmt]) -> Result<String, ParseError> {
+        let mut rendered = String::new();
+        for stmt in stmts {
+            rendered.push(' ');
+            rendered.push_str(&stmt::Visitor::visit_stmt(self, stmt)?);
+        }
+        Ok(rendered)
+    }
+}
+
 impl Visitor<String, ParseError> for AstPrinter {
     fn visit_expr(&mut self, expr: &Expr) -> Result<String, ParseError> {
         match expr {
@@ -22,7 +48,7 @@ impl Visitor<String, ParseError> for AstPrinter {
                 operator,
                 right,
             } => Ok(format!(
-                "(){} {} {})",
+                "({} {} {})",
                 self.visit_expr(left)?,
                 operator.raw,
                 self.visit_expr(right)?
@@ -30,15 +56,171 @@ impl Visitor<String, ParseError> for AstPrinter {
             Expr::Grouping { expression } => {
                 Ok(format!("(group {})", self.visit_expr(expression)?))
             }
-            Expr::Literal { value } => Ok(format!("{:?}", value.clone())),
+            Expr::Literal { value, .. } => match value {
+                LoxType::Strang(s) => Ok(format!("\"{}\"", s)),
+                _ => Ok(value.to_string()),
+            },
             Expr::Unary { operator, right } => {
                 Ok(format!("({}{})", operator.raw, self.visit_expr(right)?))
             }
             Expr::Variable { name } => Ok(name.raw.clone()),
+            Expr::This { .. } => Ok("this".to_string()),
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                let mut rendered = vec![];
+                for argument in arguments {
+                    rendered.push(self.visit_expr(argument)?);
+                }
+                Ok(format!("{}({})", self.visit_expr(callee)?, rendered.join(", ")))
+            }
+            Expr::Get { object, name } => Ok(format!("{}.{}", self.visit_expr(object)?, name.raw)),
+            Expr::Index { object, index, .. } => Ok(format!(
+                "{}[{}]",
+                self.visit_expr(object)?,
+                self.visit_expr(index)?
+            )),
             Expr::Assign { name, value } => {
                 Ok(format!("{} = {}", name.raw, self.visit_expr(value)?))
             }
-            _ => Ok(format!("haven't bothered to implement this pp yet"))
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => Ok(format!(
+                "({} ? {} : {})",
+                self.visit_expr(condition)?,
+                self.visit_expr(then_branch)?,
+                self.visit_expr(else_branch)?
+            )),
+            _ => Ok("haven't bothered to implement this pp yet".to_string())
+        }
+    }
+}
+
+impl stmt::Visitor<String, ParseError> for AstPrinter {
+    fn visit_stmt(&mut self, stmt: &Stmt) -> Result<String, ParseError> {
+        match stmt {
+            Stmt::Block { statements, .. } => Ok(format!("(block{})", self.stmts(statements)?)),
+            Stmt::Expression { expression } => Ok(format!("(; {})", self.visit_expr(expression)?)),
+            Stmt::MultiAssign { targets, values, .. } => {
+                let targets = targets
+                    .iter()
+                    .map(|target| self.visit_expr(target))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let values = values
+                    .iter()
+                    .map(|value| self.visit_expr(value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(format!("(= ({}) ({}))", targets.join(" "), values.join(" ")))
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let mut rendered = format!(
+                    "(if {} {}",
+                    self.visit_expr(condition)?,
+                    self.visit_stmt(then_branch)?
+                );
+                if let Some(else_branch) = else_branch {
+                    rendered.push_str(&format!(" {}", self.visit_stmt(else_branch)?));
+                }
+                Ok(rendered + ")")
+            }
+            Stmt::While {
+                condition,
+                then_branch,
+                increment,
+                finally_branch,
+                ..
+            } => {
+                let mut rendered = format!(
+                    "(while {} {}",
+                    self.visit_expr(condition)?,
+                    self.visit_stmt(then_branch)?
+                );
+                if let Some(increment) = increment {
+                    rendered.push_str(&format!(" (increment {})", self.visit_expr(increment)?));
+                }
+                if let Some(finally_branch) = finally_branch {
+                    rendered.push_str(&format!(" (finally {})", self.visit_stmt(finally_branch)?));
+                }
+                Ok(rendered + ")")
+            }
+            Stmt::ForEach {
+                variable,
+                iterable,
+                body,
+                ..
+            } => Ok(format!(
+                "(for {} in {} {})",
+                variable.raw,
+                self.visit_expr(iterable)?,
+                self.visit_stmt(body)?
+            )),
+            Stmt::Print { expression, .. } => Ok(format!("(print {})", self.visit_expr(expression)?)),
+            Stmt::Assert {
+                condition, message, ..
+            } => match message {
+                Some(message) => Ok(format!(
+                    "(assert {} {})",
+                    self.visit_expr(condition)?,
+                    self.visit_expr(message)?
+                )),
+                None => Ok(format!("(assert {})", self.visit_expr(condition)?)),
+            },
+            Stmt::Defer { expression, .. } => Ok(format!("(defer {})", self.visit_expr(expression)?)),
+            Stmt::Break { .. } => Ok("(break)".to_string()),
+            Stmt::Continue { .. } => Ok("(continue)".to_string()),
+            Stmt::Return { return_value, .. } => match return_value {
+                Some(value) => Ok(format!("(return {})", self.visit_expr(value)?)),
+                None => Ok("(return)".to_string()),
+            },
+            Stmt::Var {
+                name,
+                initializer,
+                is_const,
+            } => {
+                let keyword = if *is_const { "val" } else { "var" };
+                match initializer {
+                    Some(initializer) => Ok(format!(
+                        "({} {} = {})",
+                        keyword,
+                        name.raw,
+                        self.visit_expr(initializer)?
+                    )),
+                    None => Ok(format!("({} {})", keyword, name.raw)),
+                }
+            }
+            Stmt::Function {
+                name,
+                parameters,
+                body,
+                pure,
+            } => {
+                let parameters: Vec<&str> = parameters.iter().map(|p| p.raw.as_str()).collect();
+                Ok(format!(
+                    "({}funct {}({}){})",
+                    if *pure { "pure " } else { "" },
+                    name.raw,
+                    parameters.join(", "),
+                    self.stmts(body)?
+                ))
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let superclass = match superclass {
+                    Some(superclass) => format!(" < {}", self.visit_expr(superclass)?),
+                    None => String::new(),
+                };
+                Ok(format!("(class {}{}{})", name.raw, superclass, self.stmts(methods)?))
+            }
         }
     }
 }
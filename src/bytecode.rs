@@ -0,0 +1,430 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    common::{LoxType, Token, TokenType},
+    expr::Expr,
+    stmt::Stmt,
+};
+
+// a small stack-based instruction set that functions can be lowered to, for inspecting
+// what a compiled backend would run. Nothing executes it yet
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    // push constants[index]
+    Constant(usize),
+    Nil,
+    True,
+    False,
+    Pop,
+
+    // variables are looked up by name, the same way the environment does
+    DefineVariable(String),
+    GetVariable(String),
+    SetVariable(String),
+
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    ShiftLeft,
+    ShiftRight,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Negate,
+    Not,
+    Equal,
+    Greater,
+    Less,
+
+    // jumps are to an absolute instruction offset. JumpIfFalse leaves the condition on
+    // the stack, and/or want it there when they short circuit
+    Jump(usize),
+    JumpIfFalse(usize),
+    // push the named function declared in this chunk, its own listing is dumped separately
+    Function(String),
+    // call the value under the given number of arguments
+    Call(usize),
+    Print,
+    Return,
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Constant(index) => write!(f, "Constant {}", index),
+            Instruction::DefineVariable(name) => write!(f, "DefineVariable '{}'", name),
+            Instruction::GetVariable(name) => write!(f, "GetVariable '{}'", name),
+            Instruction::SetVariable(name) => write!(f, "SetVariable '{}'", name),
+            Instruction::Jump(target) => write!(f, "Jump -> {:04}", target),
+            Instruction::JumpIfFalse(target) => write!(f, "JumpIfFalse -> {:04}", target),
+            Instruction::Function(name) => write!(f, "Function '{}'", name),
+            Instruction::Call(count) => write!(f, "Call {}", count),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+// the instructions for one function, or the top level of a script
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub name: String,
+    pub code: Vec<Instruction>,
+    // the source line of each instruction
+    pub lines: Vec<u32>,
+    pub constants: Vec<LoxType>,
+}
+
+impl Chunk {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // the listing printed by --dump-bytecode. Each line is offset, source line (| when it's
+    // the same as the one above) and the instruction, with the value of any constant
+    pub fn disassemble(&self) -> String {
+        let mut listing = format!("== {} ==\n", self.name);
+        for (offset, instruction) in self.code.iter().enumerate() {
+            let line = if offset > 0 && self.lines[offset] == self.lines[offset - 1] {
+                "|".to_string()
+            } else {
+                self.lines[offset].to_string()
+            };
+            listing.push_str(&format!("{:04} {:>4} {}", offset, line, instruction));
+            if let Instruction::Constant(index) = instruction {
+                listing.push_str(&format!(" ({})", self.constants[*index]));
+            }
+            listing.push('\n');
+        }
+        listing
+    }
+}
+
+// lowers a whole program: a chunk for the top level, then one per function declared
+// anywhere in it, methods named Class.method. A function that uses something the
+// instruction set can't express yet gets an error saying what, instead of a chunk
+pub fn compile(statements: &[Stmt]) -> Vec<Result<Chunk, String>> {
+    let mut chunks = vec![compile_function("<script>", statements)];
+    collect_functions(statements, None, &mut chunks);
+    chunks
+}
+
+fn compile_function(name: &str, body: &[Stmt]) -> Result<Chunk, String> {
+    let mut compiler = Compiler::new(name);
+    for stmt in body {
+        compiler.statement(stmt)?;
+    }
+    compiler.emit(Instruction::Nil);
+    compiler.emit(Instruction::Return);
+    Ok(compiler.chunk)
+}
+
+fn collect_functions(statements: &[Stmt], class: Option<&str>, chunks: &mut Vec<Result<Chunk, String>>) {
+    for stmt in statements {
+        match stmt {
+            Stmt::Function { name, body, .. } => {
+                let name = match class {
+                    Some(class) => format!("{}.{}", class, name.raw),
+                    None => name.raw.clone(),
+                };
+                chunks.push(compile_function(&name, body));
+                collect_functions(body, None, chunks);
+            }
+            Stmt::Class { name, methods, .. } => collect_functions(methods, Some(&name.raw), chunks),
+            Stmt::Block { statements, .. } => collect_functions(statements, None, chunks),
+            Stmt::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                collect_functions(std::slice::from_ref(then_branch), None, chunks);
+                if let Some(else_branch) = else_branch {
+                    collect_functions(std::slice::from_ref(else_branch), None, chunks);
+                }
+            }
+            Stmt::While { then_branch, .. } => {
+                collect_functions(std::slice::from_ref(then_branch), None, chunks)
+            }
+            Stmt::ForEach { body, .. } => collect_functions(std::slice::from_ref(body), None, chunks),
+            _ => {}
+        }
+    }
+}
+
+struct Compiler {
+    chunk: Chunk,
+    // the line of the last token seen, for instructions from nodes without one, e.g. literals
+    line: u32,
+}
+
+impl Compiler {
+    fn new(name: &str) -> Self {
+        Self {
+            chunk: Chunk::new(name),
+            line: 1,
+        }
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.chunk.code.push(instruction);
+        self.chunk.lines.push(self.line);
+        self.chunk.code.len() - 1
+    }
+
+    fn constant(&mut self, value: LoxType) {
+        self.chunk.constants.push(value);
+        self.emit(Instruction::Constant(self.chunk.constants.len() - 1));
+    }
+
+    // point the jump at offset to the next instruction emitted
+    fn patch_jump(&mut self, offset: usize) {
+        let target = self.chunk.code.len();
+        match &mut self.chunk.code[offset] {
+            Instruction::Jump(to) | Instruction::JumpIfFalse(to) => *to = target,
+            other => unreachable!("patched {:?} as a jump", other),
+        }
+    }
+
+    fn at(&mut self, token: &Token) {
+        self.line = token.line;
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), String> {
+        self.at(stmt.token());
+        match stmt {
+            Stmt::Expression { expression } => {
+                self.expression(expression)?;
+                self.emit(Instruction::Pop);
+            }
+            Stmt::Print { expression, .. } => {
+                self.expression(expression)?;
+                self.emit(Instruction::Print);
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                match initializer {
+                    Some(initializer) => self.expression(initializer)?,
+                    None => {
+                        self.emit(Instruction::Nil);
+                    }
+                }
+                self.at(name);
+                self.emit(Instruction::DefineVariable(name.raw.clone()));
+            }
+            // scopes aren't tracked, names are resolved when they're run
+            Stmt::Block { statements, .. } => {
+                for stmt in statements {
+                    self.statement(stmt)?;
+                }
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.expression(condition)?;
+                let to_else = self.emit(Instruction::JumpIfFalse(0));
+                self.emit(Instruction::Pop);
+                self.statement(then_branch)?;
+                let to_end = self.emit(Instruction::Jump(0));
+                self.patch_jump(to_else);
+                self.emit(Instruction::Pop);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch)?;
+                }
+                self.patch_jump(to_end);
+            }
+            Stmt::While {
+                condition,
+                then_branch,
+                increment,
+                finally_branch: None,
+                ..
+            } => {
+                let start = self.chunk.code.len();
+                self.expression(condition)?;
+                let to_end = self.emit(Instruction::JumpIfFalse(0));
+                self.emit(Instruction::Pop);
+                self.statement(then_branch)?;
+                if let Some(increment) = increment {
+                    self.expression(increment)?;
+                    self.emit(Instruction::Pop);
+                }
+                self.emit(Instruction::Jump(start));
+                self.patch_jump(to_end);
+                self.emit(Instruction::Pop);
+            }
+            Stmt::Return { return_value, .. } => {
+                match return_value {
+                    Some(value) => self.expression(value)?,
+                    None => {
+                        self.emit(Instruction::Nil);
+                    }
+                }
+                self.emit(Instruction::Return);
+            }
+            Stmt::Function { name, .. } => {
+                self.emit(Instruction::Function(name.raw.clone()));
+                self.emit(Instruction::DefineVariable(name.raw.clone()));
+            }
+            other => return Err(self.unsupported(statement_kind(other))),
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<(), String> {
+        self.at(expr.token());
+        match expr {
+            Expr::Literal { value, .. } => match value {
+                LoxType::Nil => {
+                    self.emit(Instruction::Nil);
+                }
+                LoxType::Bool(true) => {
+                    self.emit(Instruction::True);
+                }
+                LoxType::Bool(false) => {
+                    self.emit(Instruction::False);
+                }
+                other => self.constant(other.clone()),
+            },
+            Expr::Grouping { expression } => self.expression(expression)?,
+            Expr::Variable { name } => {
+                self.emit(Instruction::GetVariable(name.raw.clone()));
+            }
+            Expr::Assign { name, value } => {
+                self.expression(value)?;
+                self.at(name);
+                self.emit(Instruction::SetVariable(name.raw.clone()));
+            }
+            Expr::Unary { operator, right } => {
+                self.expression(right)?;
+                self.at(operator);
+                match operator.token_type {
+                    TokenType::Minus => self.emit(Instruction::Negate),
+                    TokenType::Bang => self.emit(Instruction::Not),
+                    _ => return Err(self.unsupported(&format!("unary {}", operator.raw))),
+                };
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.at(operator);
+                let instructions: &[Instruction] = match operator.token_type {
+                    TokenType::Plus => &[Instruction::Add],
+                    TokenType::Minus => &[Instruction::Subtract],
+                    TokenType::Star => &[Instruction::Multiply],
+                    TokenType::Slash => &[Instruction::Divide],
+                    TokenType::LessLess => &[Instruction::ShiftLeft],
+                    TokenType::GreaterGreater => &[Instruction::ShiftRight],
+                    TokenType::Ampersand => &[Instruction::BitAnd],
+                    TokenType::Pipe => &[Instruction::BitOr],
+                    TokenType::Caret => &[Instruction::BitXor],
+                    TokenType::EqualEqual => &[Instruction::Equal],
+                    TokenType::BangEqual => &[Instruction::Equal, Instruction::Not],
+                    TokenType::Greater => &[Instruction::Greater],
+                    TokenType::GreaterEqual => &[Instruction::Less, Instruction::Not],
+                    TokenType::Less => &[Instruction::Less],
+                    TokenType::LessEqual => &[Instruction::Greater, Instruction::Not],
+                    _ => return Err(self.unsupported(&format!("operator {}", operator.raw))),
+                };
+                for instruction in instructions {
+                    self.emit(instruction.clone());
+                }
+            }
+            // the left operand is the result when it short circuits
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left)?;
+                if operator.token_type == TokenType::And {
+                    let to_end = self.emit(Instruction::JumpIfFalse(0));
+                    self.emit(Instruction::Pop);
+                    self.expression(right)?;
+                    self.patch_jump(to_end);
+                } else {
+                    let to_right = self.emit(Instruction::JumpIfFalse(0));
+                    let to_end = self.emit(Instruction::Jump(0));
+                    self.patch_jump(to_right);
+                    self.emit(Instruction::Pop);
+                    self.expression(right)?;
+                    self.patch_jump(to_end);
+                }
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition)?;
+                let to_else = self.emit(Instruction::JumpIfFalse(0));
+                self.emit(Instruction::Pop);
+                self.expression(then_branch)?;
+                let to_end = self.emit(Instruction::Jump(0));
+                self.patch_jump(to_else);
+                self.emit(Instruction::Pop);
+                self.expression(else_branch)?;
+                self.patch_jump(to_end);
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                self.expression(callee)?;
+                for argument in arguments {
+                    self.expression(argument)?;
+                }
+                self.at(paren);
+                self.emit(Instruction::Call(arguments.len()));
+            }
+            other => return Err(self.unsupported(expression_kind(other))),
+        }
+        Ok(())
+    }
+
+    fn unsupported(&self, what: &str) -> String {
+        format!(
+            "can't lower {} in {} at line {} to bytecode yet",
+            what, self.chunk.name, self.line
+        )
+    }
+}
+
+fn statement_kind(stmt: &Stmt) -> &'static str {
+    match stmt {
+        Stmt::While { .. } => "a loop with finally",
+        Stmt::ForEach { .. } => "a for-in loop",
+        Stmt::Assert { .. } => "assert",
+        Stmt::Defer { .. } => "defer",
+        Stmt::Break { .. } => "break",
+        Stmt::Continue { .. } => "continue",
+        Stmt::Class { .. } => "a class",
+        _ => "a statement",
+    }
+}
+
+fn expression_kind(expr: &Expr) -> &'static str {
+    match expr {
+        Expr::Get { .. } | Expr::Set { .. } => "a property",
+        Expr::This { .. } => "this",
+        Expr::Super { .. } => "super",
+        Expr::Lambda { .. } => "a lambda",
+        Expr::ArrayLiteral { .. } => "a list literal",
+        Expr::Spread { .. } => "spreading a list",
+        Expr::MapLiteral { .. } => "a map literal",
+        Expr::Index { .. } | Expr::IndexSet { .. } => "indexing",
+        Expr::Slice { .. } | Expr::SliceSet { .. } => "a slice",
+        _ => "an expression",
+    }
+}
@@ -1,10 +1,16 @@
-use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    rc::Rc,
+};
 
 use phf::phf_map;
+use serde::Serialize;
 
 use crate::{
     environment::Environment,
-    interpreter::{Interpreter, RuntimeException},
+    interpreter::{ControlFlow, Interpreter, RuntimeException},
     stmt::Stmt,
 };
 
@@ -16,6 +22,8 @@ macro_rules! token {
             raw: $raw.to_string(),
             line: $line,
             column: $column,
+            start_line: $line,
+            start_column: $column,
         }
     };
 }
@@ -33,45 +41,61 @@ macro_rules! lexer_error {
 
 pub static KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
    "and" => TokenType::And,
+   "assert" => TokenType::Assert,
    "break" => TokenType::Break,
    "class" => TokenType::Class,
+   "continue" => TokenType::Continue,
+   "defer" => TokenType::Defer,
    "else" => TokenType::Else,
    "false" => TokenType::False,
    "funct" => TokenType::Funct,
    "for" => TokenType::For,
    "finally" => TokenType::Finally,
    "if" => TokenType::If,
+   "in" => TokenType::In,
    "meth" => TokenType::Meth,
    "nil" => TokenType::Nil,
    "or" => TokenType::Or,
    "print" => TokenType::Print,
+   "pure" => TokenType::Pure,
    "return" => TokenType::Return,
    "super" => TokenType::Super,
    "this" => TokenType::This,
    "true" => TokenType::True,
+   "val" => TokenType::Val,
    "var" => TokenType::Var,
    "while" => TokenType::While,
 };
 
 pub const LOX_MAX_ARGUMENT_COUNT: usize = 255;
+pub const LOX_MAX_CALL_DEPTH: usize = 1000;
 
 #[allow(dead_code)]
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, Serialize)]
 pub enum TokenType {
     // punctuation
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
+    Ellipsis,
     SemiColon,
+    Question,
+    Colon,
 
     // operators
     Minus,
     Plus,
     Slash,
     Star,
+    MinusEqual,
+    PlusEqual,
+    SlashEqual,
+    StarEqual,
     Bang,
     BangEqual,
     Equal,
@@ -80,6 +104,11 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
+    Ampersand,
+    Pipe,
+    Caret,
 
     // literals
     Identifier,
@@ -88,23 +117,29 @@ pub enum TokenType {
 
     // keywords
     And,
+    Assert,
     Break,
     Class,
+    Continue,
+    Defer,
     Else,
     False,
     Funct,
     Finally,
     For,
     If,
+    In,
     Meth,
     Nil,
     Or,
     Print,
+    Pure,
     Return,
     Super,
     This,
     True,
     Var,
+    Val,
     While,
 
     EOF,
@@ -115,26 +150,110 @@ pub enum TokenType {
 pub struct Token {
     pub token_type: TokenType,
     pub raw: String,
+    // where the token ends, one past its last character
     pub line: u32,
     pub column: u32,
+    // where it starts, which only differs in line for strings spanning several lines
+    pub start_line: u32,
+    pub start_column: u32,
 }
-#[derive(Debug, Clone, PartialOrd)]
+#[derive(Debug, Clone)]
 pub enum LoxType {
-    Number(f32),
+    Number(f64),
     Strang(String),
     Bool(bool),
     Nil,
     Function(Rc<dyn LoxCallable>),
     Class(LoxClass),
     Instance(LoxInstance),
+    List(Rc<RefCell<Vec<Rc<RefCell<LoxType>>>>>),
+    Map(Rc<RefCell<HashMap<String, Rc<RefCell<LoxType>>>>>),
+    Lazy(Rc<RefCell<LazyState>>),
+}
+
+// a thunk made by lazy, holding the function until it's forced and its result after
+#[derive(Debug, Clone)]
+pub enum LazyState {
+    Unevaluated(Rc<dyn LoxCallable>),
+    // the function is running, forcing the thunk again from inside it would never finish
+    Evaluating,
+    Evaluated(LoxType),
 }
 
 impl PartialEq for LoxType {
     fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut Vec::new())
+    }
+}
+
+// pairs of values already being compared further up, by address. Meeting one again means
+// a cycle, which is taken as equal rather than followed round forever
+type Visited = Vec<(*const (), *const ())>;
+
+// lists and maps already copied further up, by address, so a cycle in the original becomes
+// the same cycle in the copy rather than being followed round forever
+type Copies = Vec<(*const (), LoxType)>;
+
+fn values_equal(a: &Rc<RefCell<LoxType>>, b: &Rc<RefCell<LoxType>>, visited: &mut Visited) -> bool {
+    let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+    if visited.contains(&pair) {
+        return true;
+    }
+
+    visited.push(pair);
+    let equal = a.borrow().equals(&b.borrow(), visited);
+    visited.pop();
+    equal
+}
+
+impl LoxType {
+    // a copy that shares no lists or maps with the original, all the way down. Instances,
+    // functions and thunks inside it are still shared
+    pub fn deep_copy(&self) -> LoxType {
+        self.copy_with(&mut Vec::new())
+    }
+
+    fn copy_with(&self, copies: &mut Copies) -> LoxType {
+        let address = match self {
+            Self::List(l) => Rc::as_ptr(l) as *const (),
+            Self::Map(m) => Rc::as_ptr(m) as *const (),
+            other => return other.clone(),
+        };
+        if let Some((_, copy)) = copies.iter().find(|(a, _)| *a == address) {
+            return copy.clone();
+        }
+
+        let copy_element = |e: &Rc<RefCell<LoxType>>, copies: &mut Copies| {
+            Rc::new(RefCell::new(e.borrow().copy_with(copies)))
+        };
+        match self {
+            Self::List(l) => {
+                let copy = Rc::new(RefCell::new(Vec::new()));
+                copies.push((address, Self::List(Rc::clone(&copy))));
+                let elements = l.borrow().iter().map(|e| copy_element(e, copies)).collect();
+                *copy.borrow_mut() = elements;
+                Self::List(copy)
+            }
+            Self::Map(m) => {
+                let copy = Rc::new(RefCell::new(HashMap::new()));
+                copies.push((address, Self::Map(Rc::clone(&copy))));
+                let entries = m
+                    .borrow()
+                    .iter()
+                    .map(|(k, v)| (k.clone(), copy_element(v, copies)))
+                    .collect();
+                *copy.borrow_mut() = entries;
+                Self::Map(copy)
+            }
+            _ => unreachable!("only lists and maps get this far"),
+        }
+    }
+
+    fn equals(&self, other: &Self, visited: &mut Visited) -> bool {
         match self {
             Self::Number(v) => match other {
                 Self::Number(x) => *v == *x,
-                Self::Strang(s) => match s.parse::<f32>() {
+                Self::Strang(s) => match s.parse::<f64>() {
                     Ok(x) => *v == x,
                     Err(_) => false,
                 },
@@ -142,7 +261,7 @@ impl PartialEq for LoxType {
             },
             Self::Strang(s) => match other {
                 Self::Strang(r) => s.eq(r),
-                Self::Number(v) => match s.parse::<f32>() {
+                Self::Number(v) => match s.parse::<f64>() {
                     Ok(x) => *v == x,
                     _ => false,
                 },
@@ -150,45 +269,181 @@ impl PartialEq for LoxType {
             },
             Self::Bool(b) => match other {
                 Self::Bool(c) => *b == *c,
-                Self::Nil => *b == false,
-                _ => false,
-            },
-            Self::Nil => match other {
-                Self::Bool(false) => true,
+                Self::Nil => !*b,
                 _ => false,
             },
+            Self::Nil => matches!(other, Self::Bool(false)),
             Self::Function(_) => false,
             Self::Class(c) => match other {
                 Self::Class(c2) => c.eq(c2),
                 _ => false,
             },
             Self::Instance(i) => match other {
-                Self::Instance(i2) => i.eq(i2),
+                Self::Instance(i2) => i.equals(i2, visited),
+                _ => false,
+            },
+            // lists are equal if they're the same list, or hold equal elements in the same order
+            Self::List(l) => match other {
+                Self::List(l2) => {
+                    Rc::ptr_eq(l, l2) || {
+                        let (l, l2) = (l.borrow(), l2.borrow());
+                        l.len() == l2.len()
+                            && l.iter().zip(l2.iter()).all(|(a, b)| values_equal(a, b, visited))
+                    }
+                }
+                _ => false,
+            },
+            // maps are equal if they're the same map, or hold equal values under the same keys
+            Self::Map(m) => match other {
+                Self::Map(m2) => {
+                    Rc::ptr_eq(m, m2) || {
+                        let (m, m2) = (m.borrow(), m2.borrow());
+                        m.len() == m2.len()
+                            && m.iter().all(|(k, v)| {
+                                m2.get(k).is_some_and(|v2| values_equal(v, v2, visited))
+                            })
+                    }
+                }
+                _ => false,
+            },
+            // thunks are only equal to themselves, comparing them mustn't force either
+            Self::Lazy(l) => match other {
+                Self::Lazy(l2) => Rc::ptr_eq(l, l2),
                 _ => false,
             },
         }
     }
 }
 
-impl ToString for LoxType {
-    fn to_string(&self) -> String {
+// maps have no order, so this can't be derived. Everything else orders as a derive would,
+// with values of different kinds ordered by their variant
+impl PartialOrd for LoxType {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a.partial_cmp(b),
+            (Self::Strang(a), Self::Strang(b)) => a.partial_cmp(b),
+            (Self::Bool(a), Self::Bool(b)) => a.partial_cmp(b),
+            (Self::Nil, Self::Nil) => Some(std::cmp::Ordering::Equal),
+            (Self::Function(a), Self::Function(b)) => a.partial_cmp(b),
+            (Self::Class(a), Self::Class(b)) => a.partial_cmp(b),
+            (Self::Instance(a), Self::Instance(b)) => a.partial_cmp(b),
+            (Self::List(a), Self::List(b)) => a.partial_cmp(b),
+            (Self::Map(_), Self::Map(_)) => None,
+            (Self::Lazy(_), Self::Lazy(_)) => None,
+            _ => self.variant_index().partial_cmp(&other.variant_index()),
+        }
+    }
+}
+
+impl LoxType {
+    fn variant_index(&self) -> u8 {
         match self {
-            Self::Number(v) => v.to_string(),
-            Self::Strang(v) => v.to_string(),
-            Self::Bool(v) => v.to_string(),
-            Self::Nil => "nil".to_string(),
-            Self::Function(f) => f.to_string(),
-            Self::Class(c) => c.to_string(),
-            Self::Instance(i) => i.to_string(),
+            Self::Number(_) => 0,
+            Self::Strang(_) => 1,
+            Self::Bool(_) => 2,
+            Self::Nil => 3,
+            Self::Function(_) => 4,
+            Self::Class(_) => 5,
+            Self::Instance(_) => 6,
+            Self::List(_) => 7,
+            Self::Map(_) => 8,
+            Self::Lazy(_) => 9,
+        }
+    }
+
+    // seen holds the lists and maps currently being written, so one containing itself
+    // prints as [...] or {...} rather than recursing forever
+    fn write_value(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        seen: &mut Vec<*const ()>,
+    ) -> std::fmt::Result {
+        match self {
+            // whole numbers print without a decimal point, anything else with as many digits
+            // as it takes to read back the same value. Display already does both, so keep it
+            // rather than going through an integer, which would lose -0 and huge values
+            Self::Number(v) => write!(f, "{}", v),
+            Self::Strang(v) => write!(f, "{}", v),
+            Self::Bool(v) => write!(f, "{}", v),
+            Self::Nil => write!(f, "nil"),
+            Self::Function(func) => write!(f, "{}", func),
+            Self::Class(c) => write!(f, "{}", c),
+            Self::Instance(i) => write!(f, "{}", i),
+            Self::Lazy(_) => write!(f, "<lazy>"),
+            Self::List(l) => {
+                let ptr = Rc::as_ptr(l) as *const ();
+                if seen.contains(&ptr) {
+                    return write!(f, "[...]");
+                }
+                seen.push(ptr);
+
+                write!(f, "[")?;
+                for (i, element) in l.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.borrow().write_value(f, seen)?;
+                }
+                write!(f, "]")?;
+
+                seen.pop();
+                Ok(())
+            }
+            // keys are sorted so the output is the same from run to run
+            Self::Map(m) => {
+                let ptr = Rc::as_ptr(m) as *const ();
+                if seen.contains(&ptr) {
+                    return write!(f, "{{...}}");
+                }
+                seen.push(ptr);
+
+                let m = m.borrow();
+                let mut keys: Vec<&String> = m.keys().collect();
+                keys.sort();
+
+                write!(f, "{{")?;
+                for (i, key) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", key)?;
+                    m[key].borrow().write_value(f, seen)?;
+                }
+                write!(f, "}}")?;
+
+                seen.pop();
+                Ok(())
+            }
         }
     }
 }
 
+impl Display for LoxType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_value(f, &mut vec![])
+    }
+}
+
 pub trait LoxCallable {
     fn arity(&self) -> usize;
+    // callables with optional trailing arguments take anywhere from arity to this many
+    fn max_arity(&self) -> usize {
+        self.arity()
+    }
+    // what the callable is called in a backtrace
+    fn name(&self) -> String {
+        "<native fn>".to_string()
+    }
+    // how many calls a pure function has remembered the result of, for embedders to check
+    // its cache. Always 0 for anything else
+    fn remembered_results(&self) -> usize {
+        0
+    }
+    // paren is the closing paren of the call site, for positioning any errors raised
     fn call(
         &self,
         interpreter: &mut Interpreter,
+        paren: &Token,
         arguments: Vec<Rc<RefCell<LoxType>>>,
     ) -> Result<Rc<RefCell<LoxType>>, RuntimeException>;
 }
@@ -211,17 +466,21 @@ impl PartialOrd for dyn LoxCallable {
     }
 }
 
-impl ToString for dyn LoxCallable {
-    fn to_string(&self) -> String {
-        format!("function <{}>", self.arity())
+impl Display for dyn LoxCallable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "function <{}>", self.arity())
     }
 }
 
 pub struct LoxFunction {
     name: Token,
     parameters: Vec<Token>,
-    body: Vec<Stmt>,
+    body: Rc<Vec<Stmt>>,
     closure: Rc<RefCell<Environment>>,
+    // results by arguments, only for pure functions
+    cache: Option<RefCell<HashMap<String, LoxType>>>,
+    // a class's init method, which always gives back the instance
+    is_initializer: bool,
 }
 
 impl LoxFunction {
@@ -234,8 +493,35 @@ impl LoxFunction {
         Self {
             name,
             parameters,
-            body,
+            body: Rc::new(body),
             closure,
+            cache: None,
+            is_initializer: false,
+        }
+    }
+
+    pub fn initializer(mut self) -> Self {
+        self.is_initializer = true;
+        self
+    }
+
+    // pure functions give the same result for the same arguments, so can remember them
+    pub fn memoized(mut self) -> Self {
+        self.cache = Some(RefCell::new(HashMap::new()));
+        self
+    }
+
+    // a copy of this method whose closure has `this` bound to the given instance
+    pub fn bind(&self, instance: Rc<RefCell<LoxType>>) -> LoxFunction {
+        let mut environment = Environment::new(Some(Rc::clone(&self.closure)));
+        environment.define("this".to_string(), instance);
+        Self {
+            name: self.name.clone(),
+            parameters: self.parameters.clone(),
+            body: Rc::clone(&self.body),
+            closure: Rc::new(RefCell::new(environment)),
+            cache: None,
+            is_initializer: self.is_initializer,
         }
     }
 }
@@ -245,35 +531,92 @@ impl LoxCallable for LoxFunction {
         self.parameters.len()
     }
 
+    fn name(&self) -> String {
+        self.name.raw.clone()
+    }
+
+    fn remembered_results(&self) -> usize {
+        self.cache.as_ref().map_or(0, |cache| cache.borrow().len())
+    }
+
     fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        _: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let key = self.cache.as_ref().and_then(|_| cache_key(&arguments));
+        if let (Some(cache), Some(key)) = (&self.cache, &key) {
+            if let Some(result) = cache.borrow().get(key) {
+                return Ok(Rc::new(RefCell::new(result.clone())));
+            }
+        }
+
+        let result = self.run(interpreter, arguments)?;
+
+        if let (Some(cache), Some(key)) = (&self.cache, key) {
+            if is_immutable(&result.borrow()) {
+                cache.borrow_mut().insert(key, result.borrow().clone());
+            }
+        }
+        Ok(result)
+    }
+}
+
+// lists, maps and instances can change after the call, so only calls on and results of
+// plain values are remembered
+fn is_immutable(value: &LoxType) -> bool {
+    matches!(
+        value,
+        LoxType::Number(_) | LoxType::Strang(_) | LoxType::Bool(_) | LoxType::Nil
+    )
+}
+
+fn cache_key(arguments: &[Rc<RefCell<LoxType>>]) -> Option<String> {
+    let arguments: Vec<LoxType> = arguments.iter().map(|a| a.borrow().clone()).collect();
+    arguments
+        .iter()
+        .all(is_immutable)
+        .then(|| format!("{:?}", arguments))
+}
+
+impl LoxFunction {
+    fn run(
         &self,
         interpreter: &mut Interpreter,
         arguments: Vec<Rc<RefCell<LoxType>>>,
     ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
         let mut environment = Environment::new(Some(Rc::clone(&self.closure)));
 
-        for (param, arg) in self.parameters.iter().zip(arguments.into_iter()) {
-            environment.define(param.raw.clone(), arg);
+        for (param, arg) in self.parameters.iter().zip(arguments) {
+            environment.define(param.raw.clone(), interpreter.value_to_store(arg));
         }
 
         match interpreter.execute_block(&self.body, Rc::new(RefCell::new(environment))) {
-            Err(err) => {
-                if err.token.token_type == TokenType::Return {
-                    match err.value {
-                        None => return Ok(Rc::new(RefCell::new(LoxType::Nil))),
-                        Some(v) => return Ok(v),
-                    }
-                }
+            // the resolver only lets initializers return without a value
+            Ok(()) | Err(ControlFlow::Return(None)) => {}
+            Err(ControlFlow::Return(Some(value))) => return Ok(value),
+            Err(ControlFlow::Error(err)) => return Err(err),
+            // the resolver keeps break and continue inside a loop in the same function
+            Err(ControlFlow::Break | ControlFlow::Continue) => {
+                unreachable!("break or continue escaped a function body")
             }
-            _ => {}
+        }
+        if self.is_initializer {
+            return self.closure.borrow().get(&Token {
+                token_type: TokenType::This,
+                raw: "this".to_string(),
+                ..self.name.clone()
+            });
         }
         Ok(Rc::new(RefCell::new(LoxType::Nil)))
     }
 }
 
-impl ToString for LoxFunction {
-    fn to_string(&self) -> String {
-        format!(
+impl Display for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
             "<function> {:?} ({:?})",
             self.name.raw,
             self.parameters.iter().map(|tok| &tok.raw)
@@ -281,46 +624,123 @@ impl ToString for LoxFunction {
     }
 }
 
-#[derive(Clone, PartialEq, PartialOrd, Debug)]
+#[derive(Clone)]
 pub struct LoxClass {
     name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Rc<LoxFunction>>,
 }
 
 impl LoxClass {
-    pub fn new(name: String) -> Self {
-        Self { name }
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<String, Rc<LoxFunction>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    // methods on this class override any inherited from the superclass chain
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+
+    pub fn superclass(&self) -> Option<&Rc<LoxClass>> {
+        self.superclass.as_ref()
     }
 }
 
-impl ToString for LoxClass {
-    fn to_string(&self) -> String {
-        self.name.clone()
+impl Debug for LoxClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "class {}", self.name)
+    }
+}
+
+// classes are the same class if they share a name and the very same method definitions
+impl PartialEq for LoxClass {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.superclass == other.superclass
+            && self.methods.len() == other.methods.len()
+            && self.methods.iter().all(|(name, method)| {
+                other
+                    .methods
+                    .get(name)
+                    .is_some_and(|other_method| Rc::ptr_eq(method, other_method))
+            })
+    }
+}
+
+impl PartialOrd for LoxClass {
+    fn partial_cmp(&self, _: &Self) -> Option<std::cmp::Ordering> {
+        None
+    }
+}
+
+impl Display for LoxClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
     }
 }
 
 impl LoxCallable for LoxClass {
+    // whatever init takes, if the class has one
     fn arity(&self) -> usize {
-        0
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
     }
 
     fn call(
         &self,
-        _: &mut Interpreter,
-        _: Vec<Rc<RefCell<LoxType>>>,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
     ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
-        Ok(Rc::new(RefCell::new(LoxType::Instance(LoxInstance::new(
+        let instance = Rc::new(RefCell::new(LoxType::Instance(LoxInstance::new(
             self.clone(),
-        )))))
+        ))));
+        if let Some(init) = self.find_method("init") {
+            init.bind(Rc::clone(&instance)).call(interpreter, paren, arguments)?;
+        }
+        Ok(instance)
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct LoxInstance {
     class_: LoxClass,
     fields: HashMap<String, Rc<RefCell<LoxType>>>,
 }
 
+impl PartialEq for LoxInstance {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut Vec::new())
+    }
+}
+
 impl LoxInstance {
+    // fields are compared with the visited pairs, instances can hold themselves
+    fn equals(&self, other: &Self, visited: &mut Visited) -> bool {
+        self.class_ == other.class_
+            && self.fields.len() == other.fields.len()
+            && self.fields.iter().all(|(k, v)| {
+                other
+                    .fields
+                    .get(k)
+                    .is_some_and(|v2| values_equal(v, v2, visited))
+            })
+    }
+
     pub fn new(class_: LoxClass) -> Self {
         Self {
             class_,
@@ -328,16 +748,24 @@ impl LoxInstance {
         }
     }
 
-    pub fn get(&self, name: &Token) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
-        match self.fields.get(&name.raw) {
-            Some(v) => Ok(Rc::clone(v)),
-            None => Err(RuntimeException::report(
+    // this is the value holding this instance, which methods are bound to
+    pub fn get(
+        &self,
+        name: &Token,
+        this: &Rc<RefCell<LoxType>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        // fields shadow methods
+        if let Some(v) = self.fields.get(&name.raw) {
+            return Ok(Rc::clone(v));
+        }
+
+        match self.class_.find_method(&name.raw) {
+            Some(method) => Ok(Rc::new(RefCell::new(LoxType::Function(Rc::new(
+                method.bind(Rc::clone(this)),
+            ))))),
+            None => Err(RuntimeException::new(
                 name.clone(),
-                &format!(
-                    "Property {} does not exist on {}",
-                    name.raw,
-                    self.to_string()
-                ),
+                &format!("Property {} does not exist on {}", name.raw, self),
             )),
         }
     }
@@ -345,6 +773,10 @@ impl LoxInstance {
     pub fn set(&mut self, name: &Token, value: Rc<RefCell<LoxType>>) {
         self.fields.insert(name.raw.to_string(), value);
     }
+
+    pub fn class(&self) -> &LoxClass {
+        &self.class_
+    }
 }
 
 impl PartialOrd for LoxInstance {
@@ -353,9 +785,9 @@ impl PartialOrd for LoxInstance {
     }
 }
 
-impl ToString for LoxInstance {
-    fn to_string(&self) -> String {
-        format!("{} instance", self.class_.to_string())
+impl Display for LoxInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class_)
     }
 }
 
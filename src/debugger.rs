@@ -0,0 +1,191 @@
+use std::{
+    cell::RefCell,
+    io::{BufRead, Write},
+    rc::Rc,
+};
+
+use crate::{environment::Environment, stmt::Stmt};
+
+// what to do with the input given at a pause
+#[derive(Debug, PartialEq, Eq)]
+pub enum StepAction {
+    // run the statement and pause before the next one
+    Step,
+    // print the variables in scope, then wait for another command
+    ShowEnv,
+    // stop pausing and run to completion
+    Run,
+    Unknown(String),
+}
+
+pub fn step_action(command: &str) -> StepAction {
+    match command.trim() {
+        "" | "n" | "next" => StepAction::Step,
+        "e" | "env" => StepAction::ShowEnv,
+        "c" | "continue" => StepAction::Run,
+        other => StepAction::Unknown(other.to_string()),
+    }
+}
+
+// pauses the interpreter before each statement, taking commands from input
+pub struct Debugger {
+    source: Vec<String>,
+    input: Box<dyn BufRead>,
+    running: bool,
+}
+
+impl Debugger {
+    pub fn new(source: &str, input: Box<dyn BufRead>) -> Self {
+        Self {
+            source: source.lines().map(String::from).collect(),
+            input,
+            running: false,
+        }
+    }
+
+    // returns once the statement should be run
+    pub fn before_statement(
+        &mut self,
+        stmt: &Stmt,
+        environment: &Rc<RefCell<Environment>>,
+        out: &mut dyn Write,
+    ) -> std::io::Result<()> {
+        if self.running {
+            return Ok(());
+        }
+
+        let line = stmt.token().start_line;
+        let text = self.source.get(line as usize - 1).map_or("", |l| l.trim());
+        writeln!(out, "[step] line {}: {}", line, text)?;
+
+        loop {
+            write!(out, "[step] (enter to step, env, continue) > ")?;
+            out.flush()?;
+
+            let mut command = String::new();
+            // out of input, nothing left to drive the stepping
+            if self.input.read_line(&mut command)? == 0 {
+                writeln!(out)?;
+                self.running = true;
+                return Ok(());
+            }
+
+            match step_action(&command) {
+                StepAction::Step => return Ok(()),
+                StepAction::Run => {
+                    self.running = true;
+                    return Ok(());
+                }
+                StepAction::ShowEnv => write_environment(environment, out)?,
+                StepAction::Unknown(command) => {
+                    writeln!(out, "[step] unknown command {}", command)?
+                }
+            }
+        }
+    }
+}
+
+// innermost scope first, each variable sorted by name
+fn write_environment(
+    environment: &Rc<RefCell<Environment>>,
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    let mut scope = Some(Rc::clone(environment));
+    let mut depth = 0;
+    while let Some(env) = scope {
+        let mut variables = env.borrow().variables();
+        variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        writeln!(out, "[step] scope {}:", depth)?;
+        for (name, value) in variables {
+            writeln!(out, "[step]   {} = {}", name, value.borrow())?;
+        }
+
+        scope = env.borrow().parent();
+        depth += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{common::LoxType, lox::parse_source};
+
+    const SOURCE: &str = "var a = 1;\nprint a;";
+
+    fn debugger(commands: &str) -> Debugger {
+        Debugger::new(SOURCE, Box::new(Cursor::new(commands.to_string())))
+    }
+
+    fn environment() -> Rc<RefCell<Environment>> {
+        let mut environment = Environment::new(None);
+        environment.define("a".to_string(), Rc::new(RefCell::new(LoxType::Number(1.0))));
+        Rc::new(RefCell::new(environment))
+    }
+
+    // runs the debugger before each statement of SOURCE, giving back what it wrote
+    fn step_through(debugger: &mut Debugger) -> String {
+        let (statements, environment) = (parse_source(SOURCE).unwrap(), environment());
+        let mut out = vec![];
+        for stmt in &statements {
+            debugger.before_statement(stmt, &environment, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    const PROMPT: &str = "[step] (enter to step, env, continue) > ";
+
+    #[test]
+    fn commands_map_to_actions() {
+        assert_eq!(step_action("\n"), StepAction::Step);
+        assert_eq!(step_action("next\n"), StepAction::Step);
+        assert_eq!(step_action(" env \n"), StepAction::ShowEnv);
+        assert_eq!(step_action("c"), StepAction::Run);
+        assert_eq!(step_action("jump\n"), StepAction::Unknown("jump".to_string()));
+    }
+
+    #[test]
+    fn enter_pauses_before_every_statement() {
+        let out = step_through(&mut debugger("\n\n"));
+        assert_eq!(
+            out,
+            format!("[step] line 1: var a = 1;\n{PROMPT}[step] line 2: print a;\n{PROMPT}")
+        );
+    }
+
+    #[test]
+    fn env_shows_variables_then_waits_for_another_command() {
+        let out = step_through(&mut debugger("env\n\n\n"));
+        assert_eq!(
+            out,
+            format!(
+                "[step] line 1: var a = 1;\n{PROMPT}[step] scope 0:\n[step]   a = 1\n{PROMPT}\
+                 [step] line 2: print a;\n{PROMPT}"
+            )
+        );
+    }
+
+    #[test]
+    fn continue_stops_pausing() {
+        let out = step_through(&mut debugger("continue\n"));
+        assert_eq!(out, format!("[step] line 1: var a = 1;\n{PROMPT}"));
+    }
+
+    #[test]
+    fn unknown_commands_are_reported_and_asked_again() {
+        let out = step_through(&mut debugger("jump\nc\n"));
+        assert_eq!(
+            out,
+            format!("[step] line 1: var a = 1;\n{PROMPT}[step] unknown command jump\n{PROMPT}")
+        );
+    }
+
+    #[test]
+    fn running_out_of_commands_runs_to_completion() {
+        let out = step_through(&mut debugger(""));
+        assert_eq!(out, format!("[step] line 1: var a = 1;\n{PROMPT}\n"));
+    }
+}
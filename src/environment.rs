@@ -1,4 +1,8 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use crate::{
     common::{LoxType, Token},
@@ -8,6 +12,8 @@ use crate::{
 #[derive(Debug, Clone)]
 pub struct Environment {
     values: HashMap<String, Rc<RefCell<LoxType>>>,
+    // the names among values declared with val, which can't be assigned to
+    constants: HashSet<String>,
     pub parent: Option<Rc<RefCell<Environment>>>,
 }
 
@@ -15,68 +21,86 @@ impl Environment {
     pub fn new(parent: Option<Rc<RefCell<Environment>>>) -> Self {
         Self {
             values: HashMap::new(),
+            constants: HashSet::new(),
             parent,
         }
     }
 
+    // redeclaring a constant with var makes it a plain variable again
     pub fn define(&mut self, name: String, value: Rc<RefCell<LoxType>>) {
+        self.constants.remove(&name);
+        self.values.insert(name, value);
+    }
+
+    pub fn define_const(&mut self, name: String, value: Rc<RefCell<LoxType>>) {
+        self.constants.insert(name.clone());
         self.values.insert(name, value);
     }
 
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.constants.clear();
+    }
+
+    fn check_not_const(&self, name: &Token) -> Result<(), RuntimeException> {
+        if self.constants.contains(&name.raw) {
+            return Err(RuntimeException::new(
+                name.clone(),
+                &format!("Can't assign to constant {}", name.raw),
+            ));
+        }
+        Ok(())
+    }
+
+    // the variables defined directly in this scope, not its parents
+    pub fn variables(&self) -> Vec<(String, Rc<RefCell<LoxType>>)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.clone(), Rc::clone(value)))
+            .collect()
+    }
+
     pub fn get(&self, name: &Token) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
         if let Some(val) = self.values.get(&name.raw) {
             Ok(Rc::clone(val))
         } else if let Some(ref parent) = self.parent {
-            RefCell::borrow(&parent).get(name)
+            RefCell::borrow(parent).get(name)
         } else {
-            Err(RuntimeException::report(
+            Err(RuntimeException::new(
                 name.clone(),
                 &format!("Attempted to access undefined variable {}.", name.raw),
             ))
         }
     }
 
+    // distance is how many scopes up the parent chain the variable is, as the resolver
+    // counts them: 0 for this scope, 1 for its parent and so on
     pub fn get_at(
         &self,
         distance: usize,
         name: &Token,
     ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
-        if distance == 0 {
-            match self.values.get(&name.raw) {
-                Some(v) => Ok(Rc::clone(v)),
-                None => Err(RuntimeException::report(
-                    name.clone(),
-                    &format!(
-                        "No variable with name {} at depth {}",
-                        name.raw.clone(),
-                        distance
-                    ),
-                )),
-            }
+        let value = if distance == 0 {
+            self.values.get(&name.raw).cloned()
         } else {
-            match RefCell::borrow(&self.ancestor(distance))
-                .values
-                .get(&name.raw)
-            {
-                Some(v) => Ok(Rc::clone(v)),
-                None => Err(RuntimeException::report(
-                    name.clone(),
-                    &format!(
-                        "No variable with name {} at depth {}",
-                        name.raw.clone(),
-                        distance
-                    ),
-                )),
-            }
-        }
+            RefCell::borrow(&self.ancestor(distance)).values.get(&name.raw).cloned()
+        };
+        value.ok_or_else(|| {
+            RuntimeException::new(
+                name.clone(),
+                &format!("No variable with name {} at depth {}", name.raw, distance),
+            )
+        })
     }
 
+    // the scope distance hops up the parent chain, distance must be at least 1. The scope
+    // itself isn't behind an Rc, so get_at and assign_at deal with distance 0 themselves
     fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment>> {
         let mut env = self.parent().expect("No parent scope at this distance");
         for _ in 1..distance {
             let outer = RefCell::borrow(&env)
                 .parent()
-                .expect("Global scope has no parent scope");
+                .expect("No parent scope at this distance");
             env = outer;
         }
 
@@ -93,19 +117,21 @@ impl Environment {
         value: Rc<RefCell<LoxType>>,
     ) -> Result<(), RuntimeException> {
         if self.values.contains_key(&name.raw) {
+            self.check_not_const(name)?;
             self.values.insert(name.raw.clone(), value);
-            return Ok(());
+            Ok(())
         } else if let Some(ref mut parent) = self.parent {
             parent.borrow_mut().assign(name, value)?;
-            return Ok(());
+            Ok(())
         } else {
-            Err(RuntimeException::report(
+            Err(RuntimeException::new(
                 name.clone(),
                 &format!("Attempted to assign to undefined variable {}", name.raw),
             ))
         }
     }
 
+    // distance counts scopes the same way as in get_at
     pub fn assign_at(
         &mut self,
         distance: usize,
@@ -113,26 +139,111 @@ impl Environment {
         value: Rc<RefCell<LoxType>>,
     ) -> Result<(), RuntimeException> {
         if distance == 0 {
-            match self.values.insert(name.raw.to_string(), value) {
-                Some(_) => Ok(()),
-                None => Err(RuntimeException::report(
-                    name.clone(),
-                    &format!("Unable to assign to undefined variable {}", name.raw),
-                )),
-            }
+            self.assign_here(name, value)
         } else {
-            match self
-                .ancestor(distance)
-                .borrow_mut()
-                .values
-                .insert(name.raw.to_string(), value)
-            {
-                Some(_) => Ok(()),
-                None => Err(RuntimeException::report(
-                    name.clone(),
-                    &format!("Unable to assign to undefined variable {}", name.raw),
-                )),
-            }
+            self.ancestor(distance).borrow_mut().assign_here(name, value)
+        }
+    }
+
+    // the resolver said the variable is here, if it isn't nothing is changed
+    fn assign_here(&mut self, name: &Token, value: Rc<RefCell<LoxType>>) -> Result<(), RuntimeException> {
+        if !self.values.contains_key(&name.raw) {
+            return Err(RuntimeException::new(
+                name.clone(),
+                &format!("Unable to assign to undefined variable {}", name.raw),
+            ));
+        }
+        self.check_not_const(name)?;
+        self.values.insert(name.raw.clone(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common::TokenType, token};
+
+    fn name(raw: &str) -> Token {
+        token!(Identifier, raw, (1, 1))
+    }
+
+    fn number(n: f64) -> Rc<RefCell<LoxType>> {
+        Rc::new(RefCell::new(LoxType::Number(n)))
+    }
+
+    fn value(environment: &Environment, raw: &str) -> Option<LoxType> {
+        environment.get(&name(raw)).ok().map(|v| v.borrow().clone())
+    }
+
+    // four nested scopes, each defining x as its own depth from the outermost, so the
+    // innermost sees x as 3 at distance 0, 2 at distance 1 and so on
+    fn nested() -> Environment {
+        let mut parent = None;
+        for depth in 0..3 {
+            let mut scope = Environment::new(parent);
+            scope.define("x".to_string(), number(depth as f64));
+            parent = Some(Rc::new(RefCell::new(scope)));
+        }
+        let mut innermost = Environment::new(parent);
+        innermost.define("x".to_string(), number(3.0));
+        innermost
+    }
+
+    fn x_at(environment: &Environment, distance: usize) -> LoxType {
+        environment.get_at(distance, &name("x")).unwrap().borrow().clone()
+    }
+
+    #[test]
+    fn ancestor_hops_up_the_parent_chain() {
+        let innermost = nested();
+        for distance in 1..=3 {
+            let ancestor = innermost.ancestor(distance);
+            let x = ancestor.borrow().get(&name("x")).unwrap().borrow().clone();
+            assert_eq!(x, LoxType::Number((3 - distance) as f64));
         }
+        assert!(innermost.ancestor(3).borrow().parent().is_none());
+    }
+
+    #[test]
+    fn get_at_reads_the_scope_at_each_distance() {
+        let innermost = nested();
+        assert_eq!(x_at(&innermost, 2), LoxType::Number(1.0));
+        assert_eq!(x_at(&innermost, 3), LoxType::Number(0.0));
+        assert_eq!(x_at(&innermost, 0), LoxType::Number(3.0));
+    }
+
+    #[test]
+    fn assign_at_writes_only_the_scope_at_its_distance() {
+        let mut innermost = nested();
+        innermost.assign_at(2, &name("x"), number(20.0)).unwrap();
+        innermost.assign_at(3, &name("x"), number(30.0)).unwrap();
+
+        assert_eq!(x_at(&innermost, 0), LoxType::Number(3.0));
+        assert_eq!(x_at(&innermost, 1), LoxType::Number(2.0));
+        assert_eq!(x_at(&innermost, 2), LoxType::Number(20.0));
+        assert_eq!(x_at(&innermost, 3), LoxType::Number(30.0));
+    }
+
+    #[test]
+    fn failed_assign_at_leaves_the_environment_unchanged() {
+        let outer = Rc::new(RefCell::new(Environment::new(None)));
+        outer.borrow_mut().define("a".to_string(), number(1.0));
+        outer.borrow_mut().define_const("c".to_string(), number(2.0));
+        let mut inner = Environment::new(Some(Rc::clone(&outer)));
+        inner.define("b".to_string(), number(3.0));
+
+        // not at the distance the resolver gave, even though it's in another scope
+        assert!(inner.assign_at(0, &name("a"), number(10.0)).is_err());
+        assert!(inner.assign_at(1, &name("b"), number(10.0)).is_err());
+        assert!(inner.assign_at(1, &name("c"), number(10.0)).is_err());
+        assert!(inner.assign_at(1, &name("missing"), number(10.0)).is_err());
+
+        assert_eq!(value(&inner, "a"), Some(LoxType::Number(1.0)));
+        assert_eq!(value(&inner, "b"), Some(LoxType::Number(3.0)));
+        assert_eq!(value(&inner, "c"), Some(LoxType::Number(2.0)));
+        assert_eq!(value(&inner, "missing"), None);
+        assert_eq!(inner.variables().len(), 1);
+        assert_eq!(outer.borrow().variables().len(), 2);
     }
 }
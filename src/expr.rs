@@ -1,4 +1,5 @@
 use crate::common::{LoxType, Token};
+use crate::stmt::Stmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
@@ -16,7 +17,7 @@ pub enum Expr {
     Call {
         callee: Box<Expr>,
         paren: Token,
-        arguments: Box<Vec<Expr>>
+        arguments: Vec<Expr>
     },
 
     Get {
@@ -27,7 +28,9 @@ pub enum Expr {
     Set {
         object: Box<Expr>,
         name: Token,
-        value: Box<Expr>
+        value: Box<Expr>,
+        // for compound assignment, e.g. the + of +=
+        operator: Option<Token>,
     },
 
     Grouping {
@@ -36,6 +39,8 @@ pub enum Expr {
 
     Literal {
         value: LoxType,
+        // the token the value was written as, or the for keyword of a loop without a condition
+        token: Token,
     },
 
     Logical {
@@ -52,6 +57,104 @@ pub enum Expr {
     Variable {
         name: Token,
     },
+
+    This {
+        keyword: Token,
+    },
+
+    Lambda {
+        keyword: Token,
+        parameters: Vec<Token>,
+        body: Vec<Stmt>,
+    },
+
+    ArrayLiteral {
+        bracket: Token,
+        elements: Vec<Expr>,
+    },
+
+    // ...list, only allowed as a call argument or list literal element, where the
+    // list's elements are put in its place
+    Spread {
+        ellipsis: Token,
+        expression: Box<Expr>,
+    },
+
+    MapLiteral {
+        brace: Token,
+        entries: Vec<(Expr, Expr)>,
+    },
+
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+
+    IndexSet {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+        // for compound assignment, e.g. the + of +=
+        operator: Option<Token>,
+    },
+
+    // a sub-range of a list or string, either bound can be left out
+    Slice {
+        object: Box<Expr>,
+        bracket: Token,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+    },
+
+    SliceSet {
+        object: Box<Expr>,
+        bracket: Token,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+        value: Box<Expr>,
+    },
+
+    Ternary {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
+
+    Super {
+        keyword: Token,
+        method: Token,
+    },
+}
+
+impl Expr {
+    // the first token found in an expression, for pointing at it
+    pub fn token(&self) -> &Token {
+        match self {
+            Expr::Assign { name, .. }
+            | Expr::Get { name, .. }
+            | Expr::Set { name, .. }
+            | Expr::Variable { name } => name,
+            Expr::Binary { operator, .. }
+            | Expr::Logical { operator, .. }
+            | Expr::Unary { operator, .. } => operator,
+            Expr::Call { paren, .. } => paren,
+            Expr::Spread { ellipsis, .. } => ellipsis,
+            Expr::This { keyword } | Expr::Super { keyword, .. } | Expr::Lambda { keyword, .. } => {
+                keyword
+            }
+            Expr::Index { bracket, .. }
+            | Expr::IndexSet { bracket, .. }
+            | Expr::Slice { bracket, .. }
+            | Expr::SliceSet { bracket, .. }
+            | Expr::ArrayLiteral { bracket, .. } => bracket,
+            Expr::MapLiteral { brace, .. } => brace,
+            Expr::Literal { token, .. } => token,
+            Expr::Grouping { expression } => expression.token(),
+            Expr::Ternary { condition, .. } => condition.token(),
+        }
+    }
 }
 
 pub trait Visitor<R, E> {
@@ -0,0 +1,85 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    rc::{Rc, Weak},
+};
+
+use crate::common::{LoxCallable, LoxType};
+
+type Tracked<T> = HashMap<*const (), Weak<T>>;
+
+// keeps a weak reference to every list, map, instance and closure a program makes, so once
+// it's done the ones still alive can be counted. Anything alive after the globals are
+// cleared is held by a reference cycle, and will never be freed
+#[derive(Default)]
+pub struct HeapStats {
+    lists: Tracked<RefCell<Vec<Rc<RefCell<LoxType>>>>>,
+    maps: Tracked<RefCell<HashMap<String, Rc<RefCell<LoxType>>>>>,
+    instances: Tracked<RefCell<LoxType>>,
+    closures: Tracked<dyn LoxCallable>,
+    // natives live as long as the interpreter, so aren't worth counting
+    natives: HashSet<*const ()>,
+}
+
+// the same address can be reused once what was there is freed, so a dead entry is replaced
+fn track<T: ?Sized>(tracked: &mut Tracked<T>, value: &Rc<T>) {
+    let address = Rc::as_ptr(value) as *const ();
+    let alive = tracked.get(&address).is_some_and(|weak| weak.strong_count() > 0);
+    if !alive {
+        tracked.insert(address, Rc::downgrade(value));
+    }
+}
+
+fn live<T: ?Sized>(tracked: &Tracked<T>) -> usize {
+    tracked.values().filter(|weak| weak.strong_count() > 0).count()
+}
+
+impl HeapStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ignore_native(&mut self, native: &Rc<dyn LoxCallable>) {
+        self.natives.insert(Rc::as_ptr(native) as *const ());
+    }
+
+    pub fn track(&mut self, value: &Rc<RefCell<LoxType>>) {
+        match &*value.borrow() {
+            LoxType::List(l) => track(&mut self.lists, l),
+            LoxType::Map(m) => track(&mut self.maps, m),
+            LoxType::Instance(_) => track(&mut self.instances, value),
+            LoxType::Function(f) if !self.natives.contains(&(Rc::as_ptr(f) as *const ())) => {
+                track(&mut self.closures, f)
+            }
+            _ => {}
+        }
+    }
+
+    pub fn live(&self) -> LiveCounts {
+        LiveCounts {
+            lists: live(&self.lists),
+            maps: live(&self.maps),
+            instances: live(&self.instances),
+            closures: live(&self.closures),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiveCounts {
+    pub lists: usize,
+    pub maps: usize,
+    pub instances: usize,
+    pub closures: usize,
+}
+
+impl Display for LiveCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "heap stats: {} lists, {} maps, {} instances, {} closures still alive",
+            self.lists, self.maps, self.instances, self.closures
+        )
+    }
+}
@@ -1,64 +1,264 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    io::{stdin, BufRead, Read, Write},
+    rc::Rc,
+};
 
 use crate::{
-    common::{LoxCallable, LoxClass, LoxFunction, LoxType, Token, TokenType},
+    ast_printer::AstPrinter,
+    debugger::Debugger,
+    common::{
+        LoxCallable, LoxClass, LoxFunction, LoxType, Token, TokenType, LOX_MAX_ARGUMENT_COUNT, LOX_MAX_CALL_DEPTH,
+    },
     environment::Environment,
-    expr, lox,
-    native_functions::Clock,
-    stmt,
+    heap_stats::{HeapStats, LiveCounts},
+    expr,
+    lox::{Diagnostic, ErrorReporter, Phase},
+    native_functions::{Assert, AssertThrows, Base, BinaryMath, CallIf, Clock, ClockMillis, Coalesce, Codes, Contains, Env, Exit, ExitHandler, ExtremeBy, Flush, Force, Format, HasMethod, Lazy, Len, Num, ParseFloat, ParseInt, ParseValue, Pop, ProcessExit, Push, Range, ReadAll, ReadLine, Retry, Sleep, Sleeper, Split, Str, StringFromCodes, StringMap, Stringify, Substring, ThreadSleep, TryCall, TypeOf, UnaryMath},
+    stmt, token,
 };
 
+// a lox call takes tens of KB of native stack in a debug build, so before each one the
+// stack is extended on the heap if less than the red zone is left. That way
+// LOX_MAX_CALL_DEPTH is hit before the stack overflows, on whatever thread runs lox
+const CALL_STACK_RED_ZONE: usize = 256 * 1024;
+const CALL_STACK_GROWTH: usize = 8 * 1024 * 1024;
+
+// reads through the process's shared stdin buffer rather than one of its own, so nothing
+// is read ahead and lost to other readers like the repl. Bytes looked at but not consumed
+// stay in the shared buffer
+#[derive(Default)]
+pub struct SharedStdin {
+    peeked: Vec<u8>,
+}
+
+impl Read for SharedStdin {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.peeked.clear();
+        stdin().lock().read(buf)
+    }
+}
+
+impl BufRead for SharedStdin {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.peeked = stdin().lock().fill_buf()?.to_vec();
+        Ok(&self.peeked)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.peeked.clear();
+        stdin().lock().consume(amt);
+    }
+}
+
 pub struct Interpreter {
     globals: Rc<RefCell<Environment>>,
     environment: Rc<RefCell<Environment>>,
     locals: HashMap<Token, usize>,
+    out: Box<dyn Write>,
+    // where the input native reads lines from
+    input: Box<dyn BufRead>,
+    flush_on_print: bool,
+    // whether natives may touch the world outside the interpreter, e.g. env
+    allow_io: bool,
+    // pauses before each statement when stepping through a program
+    debugger: Option<Debugger>,
+    call_depth: usize,
+    // with --backtrace, the name and call site line of every call being run, innermost last
+    call_stack: Option<Vec<(String, u32)>>,
+    // expressions waiting for each block being run to finish, innermost last
+    deferred: Vec<Vec<expr::Expr>>,
+    // names of the natives defined in globals before any script runs
+    builtins: HashSet<String>,
+    // stops scripts redefining or assigning to those names
+    protect_builtins: bool,
+    // every object made is tracked here when counting what's left alive at exit
+    heap_stats: Option<HeapStats>,
+    // lists and maps are copied into variables and parameters instead of being shared
+    value_semantics: bool,
+    // how far apart two numbers may be and still be ==, exact when None
+    float_epsilon: Option<f64>,
+    // what the exit native calls, ending the process unless replaced
+    exit_handler: Box<dyn ExitHandler>,
+    // what the sleep native waits with, really blocking the thread unless replaced
+    sleeper: Box<dyn Sleeper>,
+    // most arguments natives taking any number of them accept, shared with those natives.
+    // usize::MAX when there's no limit
+    max_args: Rc<Cell<usize>>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        Self::with_output(Box::new(std::io::stdout()))
+    }
+
+    // program output and runtime errors are written to out rather than straight to stdout
+    pub fn with_output(out: Box<dyn Write>) -> Self {
         let globals = Rc::new(RefCell::new(Environment::new(None)));
-        globals
-            .borrow_mut()
-            .define("clock".to_string(), Rc::new(RefCell::new(LoxType::Function(Rc::new(Clock)))));
+        let max_args = Rc::new(Cell::new(LOX_MAX_ARGUMENT_COUNT));
+        let natives: Vec<(&str, Rc<dyn LoxCallable>)> = vec![
+            ("clock", Rc::new(Clock)),
+            ("clock_ms", Rc::new(ClockMillis)),
+            ("stringify", Rc::new(Stringify)),
+            ("parse_value", Rc::new(ParseValue)),
+            ("flush", Rc::new(Flush)),
+            ("env", Rc::new(Env)),
+            ("len", Rc::new(Len)),
+            ("str", Rc::new(Str)),
+            ("num", Rc::new(Num)),
+            ("parse_int", Rc::new(ParseInt)),
+            ("parse_float", Rc::new(ParseFloat)),
+            ("range", Rc::new(Range)),
+            ("push", Rc::new(Push)),
+            ("pop", Rc::new(Pop)),
+            ("contains", Rc::new(Contains)),
+            ("upper", Rc::new(StringMap::new("upper", str::to_uppercase))),
+            ("lower", Rc::new(StringMap::new("lower", str::to_lowercase))),
+            ("trim", Rc::new(StringMap::new("trim", |s| s.trim().to_string()))),
+            ("substring", Rc::new(Substring)),
+            ("split", Rc::new(Split)),
+            ("codes", Rc::new(Codes)),
+            ("string_from_codes", Rc::new(StringFromCodes)),
+            ("format", Rc::new(Format::new(Rc::clone(&max_args)))),
+            ("coalesce", Rc::new(Coalesce::new(Rc::clone(&max_args)))),
+            ("retry", Rc::new(Retry)),
+            ("try_call", Rc::new(TryCall)),
+            ("min_by", Rc::new(ExtremeBy::new("min_by", false))),
+            ("max_by", Rc::new(ExtremeBy::new("max_by", true))),
+            ("exit", Rc::new(Exit)),
+            ("sleep", Rc::new(Sleep)),
+            ("assert_true", Rc::new(Assert)),
+            ("assert_throws", Rc::new(AssertThrows)),
+            ("base", Rc::new(Base)),
+            ("has_method", Rc::new(HasMethod)),
+            ("call_if", Rc::new(CallIf)),
+            ("lazy", Rc::new(Lazy)),
+            ("force", Rc::new(Force)),
+            ("input", Rc::new(ReadLine)),
+            ("read_all", Rc::new(ReadAll)),
+            ("typeof", Rc::new(TypeOf)),
+            ("sqrt", Rc::new(UnaryMath::new("sqrt", f64::sqrt))),
+            ("abs", Rc::new(UnaryMath::new("abs", f64::abs))),
+            ("floor", Rc::new(UnaryMath::new("floor", f64::floor))),
+            ("ceil", Rc::new(UnaryMath::new("ceil", f64::ceil))),
+            ("round", Rc::new(UnaryMath::new("round", f64::round))),
+            ("pow", Rc::new(BinaryMath::new("pow", f64::powf))),
+            ("min", Rc::new(BinaryMath::new("min", f64::min))),
+            ("max", Rc::new(BinaryMath::new("max", f64::max))),
+        ];
+        let builtins = natives.iter().map(|(name, _)| name.to_string()).collect();
+        for (name, native) in natives {
+            globals
+                .borrow_mut()
+                .define(name.to_string(), Rc::new(RefCell::new(LoxType::Function(native))));
+        }
 
         Self {
             globals: Rc::clone(&globals),
             environment: globals,
             locals: HashMap::new(),
+            out,
+            input: Box::new(SharedStdin::default()),
+            flush_on_print: false,
+            allow_io: true,
+            debugger: None,
+            call_depth: 0,
+            call_stack: None,
+            deferred: vec![],
+            builtins,
+            protect_builtins: false,
+            heap_stats: None,
+            value_semantics: false,
+            float_epsilon: None,
+            exit_handler: Box::new(ProcessExit),
+            sleeper: Box::new(ThreadSleep),
+            max_args,
         }
     }
 
-    fn execute(&mut self, stmt: &stmt::Stmt) -> Result<(), RuntimeException> {
+    fn execute(&mut self, stmt: &stmt::Stmt) -> Result<(), ControlFlow> {
+        // taken out while it runs, so it can look at the interpreter's state
+        if let Some(mut debugger) = self.debugger.take() {
+            debugger
+                .before_statement(stmt, &self.environment, &mut *self.out)
+                .expect("Unable to read debugger command");
+            self.debugger = Some(debugger);
+        }
         stmt::Visitor::visit_stmt(self, stmt)
     }
 
+    // runs a for loop's body with its own copy of the loop variables, then hands their
+    // values back to the loop so the increment and condition carry on from them
+    fn execute_with_fresh_bindings(
+        &mut self,
+        body: &stmt::Stmt,
+        bindings: &[Token],
+    ) -> Result<(), ControlFlow> {
+        let mut environment = Environment::new(Some(Rc::clone(&self.environment)));
+        for name in bindings {
+            let value = self.environment.borrow().get_at(0, name)?;
+            environment.define(name.raw.clone(), value);
+        }
+        let environment = Rc::new(RefCell::new(environment));
+
+        let result = self.execute_block(std::slice::from_ref(body), Rc::clone(&environment));
+        for name in bindings {
+            let value = environment.borrow().get_at(0, name)?;
+            self.environment.borrow_mut().assign_at(0, name, value)?;
+        }
+        result
+    }
+
     pub fn execute_block(
         &mut self,
         statements: &[stmt::Stmt],
         environment: Rc<RefCell<Environment>>,
-    ) -> Result<(), RuntimeException> {
+    ) -> Result<(), ControlFlow> {
         let prev = Rc::clone(&self.environment);
         self.environment = environment;
+        self.deferred.push(vec![]);
 
+        let mut result = Ok(());
         for stmt in statements {
-            match self.execute(stmt) {
-                Err(e) => {
-                    self.environment = prev;
-                    return Err(e);
-                }
-                _ => {}
+            if let Err(e) = self.execute(stmt) {
+                result = Err(e);
+                break;
             };
         }
 
+        // deferred expressions run last first, in the block's scope, even when it was left
+        // early by a return, break or error. That error wins over any they raise
+        let deferred = self.deferred.pop().unwrap_or_default();
+        for expression in deferred.iter().rev() {
+            if let Err(e) = self.evaluate(expression) {
+                if result.is_ok() {
+                    result = Err(e.into());
+                }
+            }
+        }
+
         self.environment = prev;
-        Ok(())
+        result
     }
 
     fn evaluate(&mut self, expression: &expr::Expr) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
-        expr::Visitor::visit_expr(self, expression)
+        let value = expr::Visitor::visit_expr(self, expression)?;
+        // every object a program makes passes through here on its way to being used
+        if let Some(heap_stats) = &mut self.heap_stats {
+            heap_stats.track(&value);
+        }
+        Ok(value)
     }
 
-    fn is_truthy(object: &LoxType) -> bool {
+    pub fn is_truthy(object: &LoxType) -> bool {
         match object {
             LoxType::Nil => false,
             LoxType::Bool(value) => *value,
@@ -66,10 +266,386 @@ impl Interpreter {
         }
     }
 
+    // applies an arithmetic, comparison or bitwise operator to two evaluated operands
+    fn binary(
+        &self,
+        left: Rc<RefCell<LoxType>>,
+        operator: &Token,
+        right: Rc<RefCell<LoxType>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        // TODO factor out Errs into function
+        match operator.token_type {
+            TokenType::Plus => match (&*left.borrow(), &*right.borrow()) {
+                (LoxType::Number(left), LoxType::Number(right)) => {
+                    Ok(Rc::new(RefCell::new(LoxType::Number(left + right))))
+                }
+                (LoxType::Strang(left), right) => {
+                    Ok(Rc::new(RefCell::new(LoxType::Strang(left.to_string() + &right.to_string()))))
+                }
+                (left, LoxType::Strang(right)) => {
+                    Ok(Rc::new(RefCell::new(LoxType::Strang(left.to_string() + right))))
+                }
+                (left, right) => Err(RuntimeException::new(
+                    operator.clone(),
+                    &format!("invalid operands {:?}, {:?} for +", left, right),
+                )),
+            },
+            TokenType::Minus => match (&*left.borrow(), &*right.borrow()) {
+                (LoxType::Number(left), LoxType::Number(right)) => {
+                    Ok(Rc::new(RefCell::new(LoxType::Number(left - right))))
+                }
+                (left, right) => Err(RuntimeException::new(
+                    operator.clone(),
+                    &format!("invalid operands {:?}, {:?} for -", left, right),
+                )),
+            },
+            TokenType::Slash => match (&*left.borrow(), &*right.borrow()) {
+                (LoxType::Number(left), LoxType::Number(right)) => {
+                    if *right == 0f64 {
+                        // divide by 0 error
+                        return Err(RuntimeException::new(
+                            operator.clone(),
+                            &format!("cannot divide by 0 in {:?} / {:?}", left, 0f64),
+                        ));
+                    }
+                    Ok(Rc::new(RefCell::new(LoxType::Number(left / right))))
+                }
+                (left, right) => Err(RuntimeException::new(
+                    operator.clone(),
+                    &format!("invalid operands {:?}, {:?} for / ", left, right),
+                )),
+            },
+            TokenType::Star => match (&*left.borrow(), &*right.borrow()) {
+                (LoxType::Number(left), LoxType::Number(right)) => {
+                    Ok(Rc::new(RefCell::new(LoxType::Number(left * right))))
+                }
+                (left, right) => Err(RuntimeException::new(
+                    operator.clone(),
+                    &format!("invalid operands {:?}, {:?} for * ", left, right),
+                )),
+            },
+            TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => {
+                let ordering = Interpreter::compare(&left.borrow(), &right.borrow(), operator)?;
+                let result = match operator.token_type {
+                    TokenType::Greater => ordering == Some(Ordering::Greater),
+                    TokenType::GreaterEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                    TokenType::Less => ordering == Some(Ordering::Less),
+                    _ => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                };
+                Ok(Rc::new(RefCell::new(LoxType::Bool(result))))
+            }
+            TokenType::Ampersand
+            | TokenType::Pipe
+            | TokenType::Caret
+            | TokenType::LessLess
+            | TokenType::GreaterGreater => {
+                let l = Interpreter::integer_operand(&left.borrow(), operator)?;
+                let r = Interpreter::integer_operand(&right.borrow(), operator)?;
+                let result = match operator.token_type {
+                    TokenType::Ampersand => Some(l & r),
+                    TokenType::Pipe => Some(l | r),
+                    TokenType::Caret => Some(l ^ r),
+                    TokenType::LessLess => u32::try_from(r).ok().and_then(|r| l.checked_shl(r)),
+                    _ => u32::try_from(r).ok().and_then(|r| l.checked_shr(r)),
+                };
+                match result {
+                    Some(result) => Ok(Rc::new(RefCell::new(LoxType::Number(result as f64)))),
+                    None => Err(RuntimeException::new(
+                        operator.clone(),
+                        &format!("cannot shift by {}, must be 0 to 63", r),
+                    )),
+                }
+            }
+            TokenType::BangEqual => {
+                let equal = self.values_equal(&left.borrow(), &right.borrow());
+                Ok(Rc::new(RefCell::new(LoxType::Bool(!equal))))
+            }
+            TokenType::EqualEqual => {
+                let equal = self.values_equal(&left.borrow(), &right.borrow());
+                Ok(Rc::new(RefCell::new(LoxType::Bool(equal))))
+            }
+            _ => Err(RuntimeException::new(
+                operator.clone(),
+                &format!("Invalid binary operand {:?}", operator),
+            )),
+        }
+    }
+
+    // with a float epsilon set, two numbers are equal when they're within it of each other
+    fn values_equal(&self, left: &LoxType, right: &LoxType) -> bool {
+        match (left, right, self.float_epsilon) {
+            (LoxType::Number(l), LoxType::Number(r), Some(epsilon)) => (l - r).abs() <= epsilon,
+            _ => left == right,
+        }
+    }
+
+    // only numbers with numbers and strings with strings have an order, strings by their
+    // characters. None when there's no answer, e.g. NaN, which makes every comparison false
+    fn compare(left: &LoxType, right: &LoxType, operator: &Token) -> Result<Option<Ordering>, RuntimeException> {
+        match (left, right) {
+            (LoxType::Number(left), LoxType::Number(right)) => Ok(left.partial_cmp(right)),
+            (LoxType::Strang(left), LoxType::Strang(right)) => Ok(Some(left.cmp(right))),
+            (left, right) => Err(RuntimeException::new(
+                operator.clone(),
+                &format!("invalid operands {:?}, {:?} for {}", left, right, operator.raw),
+            )),
+        }
+    }
+
+    // bitwise operators work on whole numbers, anything else is an error
+    fn integer_operand(value: &LoxType, operator: &Token) -> Result<i64, RuntimeException> {
+        match value {
+            LoxType::Number(n) if n.fract() == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 => {
+                Ok(*n as i64)
+            }
+            _ => Err(RuntimeException::new(
+                operator.clone(),
+                &format!("invalid operand {:?} for {}, expected a whole number", value, operator.raw),
+            )),
+        }
+    }
+
+    fn index_set(
+        object: &LoxType,
+        index: &LoxType,
+        bracket: &Token,
+        value: Rc<RefCell<LoxType>>,
+    ) -> Result<(), RuntimeException> {
+        match object {
+            LoxType::List(l) => {
+                let mut l = l.borrow_mut();
+                let i = Interpreter::index_into(index, l.len(), bracket)?;
+                l[i] = value;
+                Ok(())
+            }
+            LoxType::Map(m) => {
+                let key = Interpreter::map_key(index, bracket)?;
+                m.borrow_mut().insert(key, value);
+                Ok(())
+            }
+            LoxType::Strang(_) => Err(RuntimeException::new(
+                bracket.clone(),
+                "Strings are immutable, unable to assign to an index",
+            )),
+            other => Err(RuntimeException::new(
+                bracket.clone(),
+                &format!("Can only assign to an index of lists and maps, found {}", other),
+            )),
+        }
+    }
+
+    // stores an already evaluated value in a variable, property or index, for a multiple
+    // assignment. The parser only lets those through as targets
+    fn assign_to(&mut self, target: &expr::Expr, value: Rc<RefCell<LoxType>>) -> Result<(), RuntimeException> {
+        match target {
+            expr::Expr::Variable { name } => {
+                self.assign_variable(name, value)?;
+            }
+            expr::Expr::Get { object, name } => {
+                let object = self.evaluate(object)?;
+                match &mut *object.borrow_mut() {
+                    LoxType::Instance(inst) => inst.set(name, value),
+                    other => {
+                        return Err(RuntimeException::new(
+                            name.clone(),
+                            &format!("Unable to set property {} on {}. Only instances have properties.", name.raw, other),
+                        ))
+                    }
+                };
+            }
+            expr::Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                Interpreter::index_set(&object.borrow(), &index.borrow(), bracket, value)?;
+            }
+            other => unreachable!("the parser rejects {:?} as an assignment target", other),
+        }
+        Ok(())
+    }
+
+    fn assign_variable(
+        &mut self,
+        name: &Token,
+        value: Rc<RefCell<LoxType>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let value = self.value_to_store(value);
+        match self.locals.get(name) {
+            Some(d) => self.environment.borrow_mut().assign_at(*d, name, value.clone())?,
+            None => self.globals.borrow_mut().assign(name, value.clone())?,
+        };
+        Ok(value)
+    }
+
+    fn index_get(object: &LoxType, index: &LoxType, bracket: &Token) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        match object {
+            LoxType::List(l) => {
+                let l = l.borrow();
+                let i = Interpreter::index_into(index, l.len(), bracket)?;
+                Ok(Rc::clone(&l[i]))
+            }
+            // strings index by character, not byte
+            LoxType::Strang(s) => {
+                let i = Interpreter::index_into(index, s.chars().count(), bracket)?;
+                let c = s.chars().nth(i).unwrap();
+                Ok(Rc::new(RefCell::new(LoxType::Strang(c.to_string()))))
+            }
+            LoxType::Map(m) => {
+                let key = Interpreter::map_key(index, bracket)?;
+                match m.borrow().get(&key) {
+                    Some(v) => Ok(Rc::clone(v)),
+                    None => Err(RuntimeException::new(
+                        bracket.clone(),
+                        &format!("Key {} not found in map", key),
+                    )),
+                }
+            }
+            other => Err(RuntimeException::new(
+                bracket.clone(),
+                &format!("Can only index into lists, maps and strings, found {}", other),
+            )),
+        }
+    }
+
+    // checks a lox value is a whole number within bounds for something of length len
+    fn index_into(index: &LoxType, len: usize, bracket: &Token) -> Result<usize, RuntimeException> {
+        match index {
+            LoxType::Number(n) if n.fract() != 0.0 => Err(RuntimeException::new(
+                bracket.clone(),
+                &format!("Index {} is not a whole number", n),
+            )),
+            LoxType::Number(n) if *n < 0.0 || *n as usize >= len => Err(RuntimeException::new(
+                bracket.clone(),
+                &format!("Index {} out of bounds for length {}", n, len),
+            )),
+            LoxType::Number(n) => Ok(*n as usize),
+            _ => Err(RuntimeException::new(
+                bracket.clone(),
+                &format!("Index must be a number, found {}", index),
+            )),
+        }
+    }
+
+    // checks slice bounds are whole numbers with 0 <= start <= end <= len. a bound left
+    // out is the start or end of the whole thing
+    fn slice_range(
+        start: Option<&LoxType>,
+        end: Option<&LoxType>,
+        len: usize,
+        bracket: &Token,
+    ) -> Result<(usize, usize), RuntimeException> {
+        let bound = |value: Option<&LoxType>, default: usize| match value {
+            None => Ok(default),
+            Some(LoxType::Number(n)) if n.fract() != 0.0 => Err(RuntimeException::new(
+                bracket.clone(),
+                &format!("Slice bound {} is not a whole number", n),
+            )),
+            Some(LoxType::Number(n)) if *n < 0.0 || *n > len as f64 => Err(RuntimeException::new(
+                bracket.clone(),
+                &format!("Slice bound {} out of bounds for length {}", n, len),
+            )),
+            Some(LoxType::Number(n)) => Ok(*n as usize),
+            Some(other) => Err(RuntimeException::new(
+                bracket.clone(),
+                &format!("Slice bounds must be numbers, found {}", other),
+            )),
+        };
+        let (start, end) = (bound(start, 0)?, bound(end, len)?);
+        if start > end {
+            return Err(RuntimeException::new(
+                bracket.clone(),
+                &format!("Slice start {} is after its end {}", start, end),
+            ));
+        }
+        Ok((start, end))
+    }
+
+    fn evaluate_bound(&mut self, bound: &Option<Box<expr::Expr>>) -> Result<Option<LoxType>, RuntimeException> {
+        match bound {
+            Some(bound) => Ok(Some(self.evaluate(bound)?.borrow().clone())),
+            None => Ok(None),
+        }
+    }
+
+    // call arguments or list elements in order, with a spread list's elements in its place.
+    // they're copied into new cells, so the result doesn't alias the spread list
+    fn evaluate_spreading(&mut self, exprs: &[expr::Expr]) -> Result<Vec<Rc<RefCell<LoxType>>>, RuntimeException> {
+        let mut values = vec![];
+        for expr in exprs {
+            let expr::Expr::Spread { ellipsis, expression } = expr else {
+                values.push(self.evaluate(expr)?);
+                continue;
+            };
+            let spread = self.evaluate(expression)?;
+            match &*spread.borrow() {
+                LoxType::List(elements) => values.extend(
+                    elements
+                        .borrow()
+                        .iter()
+                        .map(|element| Rc::new(RefCell::new(element.borrow().clone()))),
+                ),
+                other => {
+                    return Err(RuntimeException::new(
+                        ellipsis.clone(),
+                        &format!("Can only spread a list, found {}", other),
+                    ))
+                }
+            };
+        }
+        Ok(values)
+    }
+
+    // map keys are strings, numbers are converted to their string form
+    fn map_key(key: &LoxType, token: &Token) -> Result<String, RuntimeException> {
+        match key {
+            LoxType::Strang(s) => Ok(s.clone()),
+            LoxType::Number(n) => Ok(n.to_string()),
+            _ => Err(RuntimeException::new(
+                token.clone(),
+                &format!("Map keys must be strings or numbers, found {}", key),
+            )),
+        }
+    }
+
     pub fn globals(&self) -> Rc<RefCell<Environment>> {
         Rc::clone(&self.globals)
     }
 
+    // a global's current value, for hosts embedding the interpreter
+    pub fn get_global(&self, name: &str) -> Option<LoxType> {
+        let name = token!(Identifier, name, (0, 0));
+        self.globals.borrow().get(&name).ok().map(|v| v.borrow().clone())
+    }
+
+    // calls a lox function or class from rust, e.g. a callback fetched with get_global.
+    // errors carry line 0, there's no call in the source to point at
+    pub fn call_value(&mut self, callee: LoxType, args: Vec<LoxType>) -> Result<LoxType, RuntimeException> {
+        let paren = token!(RightParen, ")", (0, 0));
+        let callable = Interpreter::callable(&callee, &paren)?;
+        let args = args.into_iter().map(|a| Rc::new(RefCell::new(a))).collect();
+
+        let result = self.call_function(callable.as_ref(), &paren, args)?;
+        let result = result.borrow().clone();
+        Ok(result)
+    }
+
+    fn callable(callee: &LoxType, paren: &Token) -> Result<Rc<dyn LoxCallable>, RuntimeException> {
+        match callee {
+            LoxType::Function(f) => Ok(Rc::clone(f)),
+            LoxType::Class(c) => Ok(Rc::new(c.clone())),
+            _ => Err(RuntimeException::new(
+                paren.clone(),
+                &format!(
+                    "Unable to call {:?}. Only functions and classes may be called",
+                    callee
+                ),
+            )),
+        }
+    }
+
     pub fn resolve(&mut self, name: Token, depth: usize) {
         self.locals.insert(name, depth);
     }
@@ -77,24 +653,282 @@ impl Interpreter {
     pub fn lookup_variable(&mut self, name: &Token) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
         let distance = self.locals.get(name);
         match distance {
-            Some(d) => self.environment.borrow().get_at(*d, &name),
-            None => self.globals.borrow().get(&name),
+            Some(d) => self.environment.borrow().get_at(*d, name),
+            None => self.globals.borrow().get(name),
         }
     }
 
-    pub fn interpret(&mut self, statements: &[stmt::Stmt]) {
+    // every call into a LoxCallable goes through here, whether it comes from lox code
+    // or from a native calling back into lox, so the depth limit can't be sidestepped
+    pub fn call_function(
+        &mut self,
+        callable: &dyn LoxCallable,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let (arity, max_arity) = (callable.arity(), callable.max_arity());
+        if arguments.len() < arity || arguments.len() > max_arity {
+            let expected = if arity == max_arity {
+                arity.to_string()
+            } else if max_arity == usize::MAX {
+                format!("at least {}", arity)
+            } else {
+                format!("{} to {}", arity, max_arity)
+            };
+            return Err(RuntimeException::new(
+                paren.clone(),
+                &format!("Expected {} arguments, found {}", expected, arguments.len()),
+            ));
+        }
+
+        if self.call_depth >= LOX_MAX_CALL_DEPTH {
+            return Err(RuntimeException::new(
+                paren.clone(),
+                &format!("Exceeded max call depth of {}", LOX_MAX_CALL_DEPTH),
+            ));
+        }
+
+        if let Some(call_stack) = &mut self.call_stack {
+            call_stack.push((callable.name(), paren.line));
+        }
+        self.call_depth += 1;
+        let mut result = stacker::maybe_grow(CALL_STACK_RED_ZONE, CALL_STACK_GROWTH, || {
+            callable.call(self, paren, arguments)
+        });
+        self.call_depth -= 1;
+        if let Some(call_stack) = &mut self.call_stack {
+            // the first call an error leaves is the one it was raised in, so the stack
+            // still holds every call leading to it
+            if let Err(err) = &mut result {
+                if err.backtrace.is_none() {
+                    err.backtrace = Some(
+                        call_stack
+                            .iter()
+                            .rev()
+                            .map(|(name, line)| format!("  in {}, called at line {}", name, line))
+                            .collect(),
+                    );
+                }
+            }
+            call_stack.pop();
+        }
+        result
+    }
+
+    // print the chain of calls leading to a runtime error along with it
+    pub fn set_backtrace(&mut self, backtrace: bool) {
+        self.call_stack = backtrace.then(Vec::new);
+    }
+
+    pub fn interpret(&mut self, statements: &[stmt::Stmt], reporter: &ErrorReporter) {
         for stmt in statements {
-            if let Err(_) = self.execute(stmt) {
-                return;
+            let err = match self.execute(stmt) {
+                Ok(()) => continue,
+                Err(ControlFlow::Error(err)) => err,
+                // the resolver keeps break and continue inside loops and return inside functions
+                Err(_) => unreachable!("control flow escaped to the top level"),
+            };
+            Interpreter::report(err, reporter);
+            return;
+        }
+    }
+
+    // like interpret, but a lone expression statement has its value printed rather than
+    // thrown away, unless it's nil, as the repl wants
+    pub fn interpret_echoing(&mut self, statements: &[stmt::Stmt], reporter: &ErrorReporter) {
+        let expression = match statements {
+            [stmt::Stmt::Expression { expression }] => expression,
+            _ => return self.interpret(statements, reporter),
+        };
+        match self.evaluate(expression) {
+            Ok(value) if matches!(*value.borrow(), LoxType::Nil) => {}
+            Ok(value) => self.print_value(&value.borrow()),
+            Err(err) => Interpreter::report(err, reporter),
+        }
+    }
+
+    fn report(err: RuntimeException, reporter: &ErrorReporter) {
+        let diagnostic = Diagnostic::new(
+            Phase::Runtime,
+            format!("{} caused by {:?}", err.message, err.token.token_type),
+            err.token.line,
+            err.token.column,
+        )
+        .starting_at(&err.token);
+        reporter.report_runtime_error(diagnostic.with_notes(err.backtrace.unwrap_or_default()));
+    }
+
+    fn print_value(&mut self, value: &LoxType) {
+        writeln!(self.out, "{}", value).expect("Unable to write to interpreter output");
+        if self.flush_on_print {
+            self.flush().expect("Unable to flush interpreter output");
+        }
+    }
+
+    pub fn write_error(&mut self, message: &str) {
+        self.write_line(message);
+    }
+
+    // a line of output that isn't the program's, e.g. the repl listing globals
+    pub fn write_line(&mut self, line: &str) {
+        writeln!(self.out, "{}", line).expect("Unable to write to interpreter output");
+        if self.flush_on_print {
+            self.flush().expect("Unable to flush interpreter output");
+        }
+    }
+
+    // by default output is flushed whenever the sink decides to, e.g. per line for stdout
+    pub fn set_flush_on_print(&mut self, flush_on_print: bool) {
+        self.flush_on_print = flush_on_print;
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn set_debugger(&mut self, debugger: Option<Debugger>) {
+        self.debugger = debugger;
+    }
+
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        self.input = input;
+    }
+
+    // one line of input without its line ending, None once the input is exhausted
+    pub fn read_line(&mut self) -> std::io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    // everything left on the input, line endings and all
+    pub fn read_all_input(&mut self) -> std::io::Result<String> {
+        let mut contents = String::new();
+        self.input.read_to_string(&mut contents)?;
+        Ok(contents)
+    }
+
+    pub fn write_prompt(&mut self, prompt: &str) -> std::io::Result<()> {
+        write!(self.out, "{}", prompt)?;
+        self.out.flush()
+    }
+
+    // embedders running untrusted code can turn this off to sandbox it
+    pub fn set_allow_io(&mut self, allow_io: bool) {
+        self.allow_io = allow_io;
+    }
+
+    pub fn allow_io(&self) -> bool {
+        self.allow_io
+    }
+
+    pub fn set_protect_builtins(&mut self, protect_builtins: bool) {
+        self.protect_builtins = protect_builtins;
+    }
+
+    // start counting the objects programs make, natives already in globals aren't counted
+    pub fn enable_heap_stats(&mut self) {
+        let mut heap_stats = HeapStats::new();
+        for (_, value) in self.globals.borrow().variables() {
+            if let LoxType::Function(native) = &*value.borrow() {
+                heap_stats.ignore_native(native);
             }
         }
+        self.heap_stats = Some(heap_stats);
+    }
+
+    // how many objects outlive the program. Clears the globals first so only objects kept
+    // alive by reference cycles are left, which leaves the interpreter unusable after
+    pub fn finish_heap_stats(&mut self) -> Option<LiveCounts> {
+        let heap_stats = self.heap_stats.take()?;
+        self.globals.borrow_mut().clear();
+        Some(heap_stats.live())
+    }
+
+    // embedders that mustn't have the process ended under them can handle exit themselves
+    pub fn set_exit_handler(&mut self, exit_handler: Box<dyn ExitHandler>) {
+        self.exit_handler = exit_handler;
+    }
+
+    // output written so far is flushed first, in case the handler ends the process. Gives
+    // back the error to stop the script with if it doesn't
+    pub fn exit(&mut self, code: i32) -> String {
+        self.flush().expect("Unable to flush interpreter output");
+        self.exit_handler.exit(code)
+    }
+
+    pub fn set_sleeper(&mut self, sleeper: Box<dyn Sleeper>) {
+        self.sleeper = sleeper;
+    }
+
+    // output written so far is flushed first, so it shows before the pause
+    pub fn sleep(&mut self, duration: std::time::Duration) {
+        self.flush().expect("Unable to flush interpreter output");
+        self.sleeper.sleep(duration);
+    }
+
+    // None lets format and coalesce take any number of arguments
+    pub fn set_max_args(&mut self, max_args: Option<usize>) {
+        self.max_args.set(max_args.unwrap_or(usize::MAX));
+    }
+
+    pub fn set_float_epsilon(&mut self, float_epsilon: Option<f64>) {
+        self.float_epsilon = float_epsilon;
+    }
+
+    pub fn set_value_semantics(&mut self, value_semantics: bool) {
+        self.value_semantics = value_semantics;
+    }
+
+    // what actually goes into a variable or parameter being given value. With value
+    // semantics that's a deep copy, so changing a list through one name can't be seen
+    // through another
+    pub fn value_to_store(&mut self, value: Rc<RefCell<LoxType>>) -> Rc<RefCell<LoxType>> {
+        if !self.value_semantics || !matches!(&*value.borrow(), LoxType::List(_) | LoxType::Map(_)) {
+            return value;
+        }
+        let copy = Rc::new(RefCell::new(value.borrow().deep_copy()));
+        if let Some(heap_stats) = &mut self.heap_stats {
+            heap_stats.track(&copy);
+        }
+        copy
+    }
+
+    // lets embedders give scripts their own natives. They count as builtins, so
+    // protect_builtins covers them too
+    pub fn register_native(&mut self, name: &str, native: Rc<dyn LoxCallable>) {
+        if let Some(heap_stats) = &mut self.heap_stats {
+            heap_stats.ignore_native(&native);
+        }
+        self.builtins.insert(name.to_string());
+        self.globals
+            .borrow_mut()
+            .define(name.to_string(), Rc::new(RefCell::new(LoxType::Function(native))));
+    }
+
+    // whether name is a native that scripts aren't allowed to replace
+    pub fn is_protected_builtin(&self, name: &str) -> bool {
+        self.protect_builtins && self.is_builtin(name)
+    }
+
+    // whether name is one of the natives defined before any script runs
+    pub fn is_builtin(&self, name: &str) -> bool {
+        self.builtins.contains(name)
     }
 }
 
 impl expr::Visitor<Rc<RefCell<LoxType>>, RuntimeException> for Interpreter {
     fn visit_expr(&mut self, expr: &expr::Expr) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
         match expr {
-            expr::Expr::Literal { value } => Ok(Rc::new(RefCell::new(value.clone()))),
+            expr::Expr::Literal { value, .. } => Ok(Rc::new(RefCell::new(value.clone()))),
             expr::Expr::Logical {
                 left,
                 operator,
@@ -102,19 +936,20 @@ impl expr::Visitor<Rc<RefCell<LoxType>>, RuntimeException> for Interpreter {
             } => {
                 let left = self.evaluate(left)?;
 
+                // short circuiting yields the operand itself, not a bool
                 match operator.token_type {
                     TokenType::Or => {
-                        if Interpreter::is_truthy(&*left.borrow()) {
-                            return Ok(Rc::new(RefCell::new(LoxType::Bool(true))));
+                        if Interpreter::is_truthy(&left.borrow()) {
+                            return Ok(left);
                         }
                     }
                     TokenType::And => {
-                        if !Interpreter::is_truthy(&*left.borrow()) {
-                            return Ok(Rc::new(RefCell::new(LoxType::Bool(false))));
+                        if !Interpreter::is_truthy(&left.borrow()) {
+                            return Ok(left);
                         }
                     }
                     _ => {
-                        return Err(RuntimeException::report(
+                        return Err(RuntimeException::new(
                             operator.clone(),
                             &format!("invalid operator {} in logical expression", operator.raw),
                         ))
@@ -132,68 +967,7 @@ impl expr::Visitor<Rc<RefCell<LoxType>>, RuntimeException> for Interpreter {
                 let left = self.evaluate(left)?;
                 let right = self.evaluate(right)?;
 
-                // TODO factor out Errs into function
-                match operator.token_type {
-                    TokenType::Plus => match (&*left.borrow(), &*right.borrow()) {
-                        (LoxType::Number(left), LoxType::Number(right)) => {
-                            Ok(Rc::new(RefCell::new(LoxType::Number(left + right))))
-                        }
-                        (LoxType::Strang(left), right) => {
-                            Ok(Rc::new(RefCell::new(LoxType::Strang(left.to_string() + &right.to_string()))))
-                        }
-                        (left, LoxType::Strang(right)) => {
-                            Ok(Rc::new(RefCell::new(LoxType::Strang(left.to_string() + &right))))
-                        }
-                        (left, right) => Err(RuntimeException::report(
-                            operator.clone(),
-                            &format!("invalid operands {:?}, {:?} for +", left, right),
-                        )),
-                    },
-                    TokenType::Minus => match (&*left.borrow(), &*right.borrow()) {
-                        (LoxType::Number(left), LoxType::Number(right)) => {
-                            Ok(Rc::new(RefCell::new(LoxType::Number(left - right))))
-                        }
-                        (left, right) => Err(RuntimeException::report(
-                            operator.clone(),
-                            &format!("invalid operands {:?}, {:?} for -", left, right),
-                        )),
-                    },
-                    TokenType::Slash => match (&*left.borrow(), &*right.borrow()) {
-                        (LoxType::Number(left), LoxType::Number(right)) => {
-                            if *right == 0f32 {
-                                // divide by 0 error
-                                return Err(RuntimeException::report(
-                                    operator.clone(),
-                                    &format!("cannot divide by 0 in {:?} / {:?}", left, 0f32),
-                                ));
-                            }
-                            Ok(Rc::new(RefCell::new(LoxType::Number(left / right))))
-                        }
-                        (left, right) => Err(RuntimeException::report(
-                            operator.clone(),
-                            &format!("invalid operands {:?}, {:?} for / ", left, right),
-                        )),
-                    },
-                    TokenType::Star => match (&*left.borrow(), &*right.borrow()) {
-                        (LoxType::Number(left), LoxType::Number(right)) => {
-                            Ok(Rc::new(RefCell::new(LoxType::Number(left * right))))
-                        }
-                        (left, right) => Err(RuntimeException::report(
-                            operator.clone(),
-                            &format!("invalid operands {:?}, {:?} for * ", left, right),
-                        )),
-                    },
-                    TokenType::Greater => Ok(Rc::new(RefCell::new(LoxType::Bool(left > right)))),
-                    TokenType::GreaterEqual => Ok(Rc::new(RefCell::new(LoxType::Bool(left >= right)))),
-                    TokenType::Less => Ok(Rc::new(RefCell::new(LoxType::Bool(left < right)))),
-                    TokenType::LessEqual => Ok(Rc::new(RefCell::new(LoxType::Bool(left <= right)))),
-                    TokenType::BangEqual => Ok(Rc::new(RefCell::new(LoxType::Bool(!(left == right))))),
-                    TokenType::EqualEqual => Ok(Rc::new(RefCell::new(LoxType::Bool(left == right)))),
-                    _ => Err(RuntimeException::report(
-                        operator.clone(),
-                        &format!("Invalid binary operand {:?}", operator),
-                    )),
-                }
+                self.binary(left, operator, right)
             }
             expr::Expr::Grouping { expression } => Ok(self.evaluate(expression)?),
             expr::Expr::Unary { operator, right } => {
@@ -202,7 +976,7 @@ impl expr::Visitor<Rc<RefCell<LoxType>>, RuntimeException> for Interpreter {
                 match operator.token_type {
                     TokenType::Minus => match &*right.borrow() {
                         LoxType::Number(value) => Ok(Rc::new(RefCell::new(LoxType::Number(-value)))),
-                        _ => Err(RuntimeException::report(
+                        _ => Err(RuntimeException::new(
                             operator.clone(),
                             &format!(
                                 "Unary operator Minus '-' not supported on type of {:?}",
@@ -211,9 +985,9 @@ impl expr::Visitor<Rc<RefCell<LoxType>>, RuntimeException> for Interpreter {
                         )),
                     },
                     TokenType::Bang => {
-                        return Ok(Rc::new(RefCell::new(LoxType::Bool(!Interpreter::is_truthy(&*right.borrow())))));
+                        return Ok(Rc::new(RefCell::new(LoxType::Bool(!Interpreter::is_truthy(&right.borrow())))));
                     }
-                    _ => Err(RuntimeException::report(
+                    _ => Err(RuntimeException::new(
                         operator.clone(),
                         &format!(
                             "Unary operator Bang '!' not supported on type of {:?}",
@@ -229,94 +1003,246 @@ impl expr::Visitor<Rc<RefCell<LoxType>>, RuntimeException> for Interpreter {
             } => {
                 let callee = self.evaluate(callee)?;
 
-                let mut args = vec![];
-                for arg in arguments.iter() {
-                    args.push(self.evaluate(arg)?);
+                let args = self.evaluate_spreading(arguments)?;
+                let callable = Interpreter::callable(&callee.borrow(), paren)?;
+                self.call_function(callable.as_ref(), paren, args)
+            }
+            expr::Expr::Variable { name } => self.lookup_variable(name),
+            expr::Expr::This { keyword } => self.lookup_variable(keyword),
+            expr::Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let value = Interpreter::index_get(&object.borrow(), &index.borrow(), bracket)?;
+                Ok(value)
+            }
+            expr::Expr::IndexSet {
+                object,
+                bracket,
+                index,
+                value,
+                operator,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let mut value = self.evaluate(value)?;
+                // compound assignment, the element is read through the same object and index
+                if let Some(operator) = operator {
+                    let current = Interpreter::index_get(&object.borrow(), &index.borrow(), bracket)?;
+                    value = self.binary(current, operator, value)?;
                 }
-                
-                let x = &*callee.borrow();
-                match x {
-                    LoxType::Function(f) => {
-                        if args.len() != f.arity() {
-                            Err(RuntimeException::report(
-                                paren.clone(),
-                                &format!(
-                                    "Expected {} arguments, found {} in {:?}",
-                                    f.arity(),
-                                    arguments.len(),
-                                    arguments
-                                ),
-                            ))
-                        } else {
-                            f.call(self, args)
-                        }
+                Interpreter::index_set(&object.borrow(), &index.borrow(), bracket, Rc::clone(&value))?;
+                Ok(value)
+            }
+            expr::Expr::Slice {
+                object,
+                bracket,
+                start,
+                end,
+            } => {
+                let object = self.evaluate(object)?;
+                let (start, end) = (self.evaluate_bound(start)?, self.evaluate_bound(end)?);
+                let sliced = match &*object.borrow() {
+                    LoxType::List(l) => {
+                        let l = l.borrow();
+                        let (start, end) = Interpreter::slice_range(start.as_ref(), end.as_ref(), l.len(), bracket)?;
+                        LoxType::List(Rc::new(RefCell::new(l[start..end].to_vec())))
                     }
-                    LoxType::Class(c) => {
-                        if args.len() != c.arity() {
-                            Err(RuntimeException::report(
-                                paren.clone(),
-                                &format!(
-                                    "Expected {} arguments, found {} in {:?}",
-                                    c.arity(),
-                                    arguments.len(),
-                                    arguments
-                                ),
-                            ))
-                        }
-                        else {
-                            c.call(self, args)
-                        }
+                    // strings slice by character, not byte
+                    LoxType::Strang(s) => {
+                        let len = s.chars().count();
+                        let (start, end) = Interpreter::slice_range(start.as_ref(), end.as_ref(), len, bracket)?;
+                        LoxType::Strang(s.chars().skip(start).take(end - start).collect())
                     }
-                    _ => Err(RuntimeException::report(
-                        paren.clone(),
-                        &format!(
-                            "Unable to call {:?}. Only functions and classes may be called",
-                            callee
-                        ),
+                    other => {
+                        return Err(RuntimeException::new(
+                            bracket.clone(),
+                            &format!("Can only slice lists and strings, found {}", other),
+                        ))
+                    }
+                };
+                Ok(Rc::new(RefCell::new(sliced)))
+            }
+            expr::Expr::SliceSet {
+                object,
+                bracket,
+                start,
+                end,
+                value,
+            } => {
+                let object = self.evaluate(object)?;
+                let (start, end) = (self.evaluate_bound(start)?, self.evaluate_bound(end)?);
+                let value = self.evaluate(value)?;
+                // copied out first, the replacement may be the list being assigned into
+                let replacement = match &*value.borrow() {
+                    LoxType::List(l) => l.borrow().clone(),
+                    other => {
+                        return Err(RuntimeException::new(
+                            bracket.clone(),
+                            &format!("Can only assign a list to a slice, found {}", other),
+                        ))
+                    }
+                };
+                match &*object.borrow() {
+                    LoxType::List(l) => {
+                        let mut l = l.borrow_mut();
+                        let (start, end) = Interpreter::slice_range(start.as_ref(), end.as_ref(), l.len(), bracket)?;
+                        l.splice(start..end, replacement);
+                    }
+                    LoxType::Strang(_) => {
+                        return Err(RuntimeException::new(
+                            bracket.clone(),
+                            "Strings are immutable, unable to assign to a slice",
+                        ))
+                    }
+                    other => {
+                        return Err(RuntimeException::new(
+                            bracket.clone(),
+                            &format!("Can only assign to a slice of a list, found {}", other),
+                        ))
+                    }
+                }
+                Ok(value)
+            }
+            expr::Expr::Lambda {
+                keyword,
+                parameters,
+                body,
+            } => {
+                let name = Token {
+                    token_type: TokenType::Identifier,
+                    raw: "anonymous".to_string(),
+                    ..keyword.clone()
+                };
+                let function = LoxFunction::new(
+                    name,
+                    parameters.to_vec(),
+                    body.to_vec(),
+                    Rc::clone(&self.environment),
+                );
+                Ok(Rc::new(RefCell::new(LoxType::Function(Rc::new(function)))))
+            }
+            expr::Expr::ArrayLiteral { elements, .. } => {
+                let values = self.evaluate_spreading(elements)?;
+                Ok(Rc::new(RefCell::new(LoxType::List(Rc::new(RefCell::new(values))))))
+            }
+            // the parser only allows a spread where evaluate_spreading will see it
+            expr::Expr::Spread { ellipsis, .. } => Err(RuntimeException::new(
+                ellipsis.clone(),
+                "A list can only be spread into call arguments or a list literal",
+            )),
+            expr::Expr::MapLiteral { brace, entries } => {
+                let mut map = HashMap::new();
+                for (key, value) in entries {
+                    let key = Interpreter::map_key(&self.evaluate(key)?.borrow(), brace)?;
+                    map.insert(key, self.evaluate(value)?);
+                }
+                Ok(Rc::new(RefCell::new(LoxType::Map(Rc::new(RefCell::new(map))))))
+            }
+            expr::Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.evaluate(condition)?;
+                // only the taken branch is evaluated
+                if Interpreter::is_truthy(&condition.borrow()) {
+                    self.evaluate(then_branch)
+                } else {
+                    self.evaluate(else_branch)
+                }
+            }
+            expr::Expr::Super { keyword, method } => {
+                let distance = *self
+                    .locals
+                    .get(keyword)
+                    .expect("super is always resolved to a local");
+                let superclass = self.environment.borrow().get_at(distance, keyword)?;
+                // `this` always lives in the environment just inside the one holding `super`
+                let this = self
+                    .environment
+                    .borrow()
+                    .get_at(distance - 1, &token!(This, "this", (keyword.line, keyword.column)))?;
+
+                let found = match &*superclass.borrow() {
+                    LoxType::Class(c) => c.find_method(&method.raw),
+                    _ => None,
+                };
+                match found {
+                    Some(m) => Ok(Rc::new(RefCell::new(LoxType::Function(Rc::new(m.bind(this)))))),
+                    None => Err(RuntimeException::new(
+                        method.clone(),
+                        &format!("Undefined property {} on superclass", method.raw),
                     )),
                 }
             }
-            expr::Expr::Variable { name } => self.lookup_variable(name),
             expr::Expr::Assign { name, value } => {
                 let value = self.evaluate(value)?;
-                let distance = self.locals.get(name);
-                match distance {
-                    Some(d) => self
-                        .environment
-                        .borrow_mut()
-                        .assign_at(*d, name, value.clone())?,
-                    None => self.globals.borrow_mut().assign(name, value.clone())?,
-                };
-                Ok(value)
+                self.assign_variable(name, value)
             }
             expr::Expr::Get { object, name } => {
                 let object = self.evaluate(object)?;
                 let x = &*object.borrow();
                 match x {
                     LoxType::Instance(inst) => {
-                        inst.get(name)
+                        inst.get(name, &object)
+                    }
+                    // lists and strings aren't instances, but have a few computed properties
+                    LoxType::List(l) => {
+                        let l = l.borrow();
+                        match name.raw.as_str() {
+                            "length" => Ok(Rc::new(RefCell::new(LoxType::Number(l.len() as f64)))),
+                            "first" | "last" => {
+                                let element = if name.raw == "first" { l.first() } else { l.last() };
+                                match element {
+                                    Some(e) => Ok(Rc::clone(e)),
+                                    None => Err(RuntimeException::new(name.clone(), &format!("Unable to get {} of an empty list", name.raw))),
+                                }
+                            }
+                            _ => Err(RuntimeException::new(name.clone(), &format!("Lists have no property {}", name.raw))),
+                        }
+                    }
+                    LoxType::Strang(s) if name.raw == "length" => {
+                        Ok(Rc::new(RefCell::new(LoxType::Number(s.chars().count() as f64))))
                     }
-                    _ => Err(RuntimeException::report(name.clone(), &format!("Unable to access property {} on {:?}. Not an instance. Only instances have properties.", name.raw, object)))
+                    _ => Err(RuntimeException::new(name.clone(), &format!("Unable to access property {} on {:?}. Not an instance. Only instances have properties.", name.raw, object)))
                 }
             },
-            expr::Expr::Set { object, name, value } => {
+            expr::Expr::Set {
+                object,
+                name,
+                value,
+                operator,
+            } => {
                 let object = self.evaluate(object)?;
-                let x = &mut *object.borrow_mut();
-                match x {
-                    LoxType::Instance(ref mut inst) => {
-                        let value = self.evaluate(value)?;
-                        inst.set(name, value.clone());
-                        Ok(value)
-                    } 
-                    _ => Err(RuntimeException::report(name.clone(), &format!("Unable to set property on {} on {:?}. Not an instance. Only instances have properties.", name.raw, object)))
+                if !matches!(&*object.borrow(), LoxType::Instance(_)) {
+                    return Err(RuntimeException::new(name.clone(), &format!("Unable to set property on {} on {:?}. Not an instance. Only instances have properties.", name.raw, object)));
+                }
+
+                // the value may read from the object itself, so only borrow it mutably once evaluated
+                let mut value = self.evaluate(value)?;
+                // compound assignment, the property is read through the same object
+                if let Some(operator) = operator {
+                    let current = match &*object.borrow() {
+                        LoxType::Instance(inst) => inst.get(name, &object)?,
+                        _ => unreachable!("checked to be an instance above"),
+                    };
+                    value = self.binary(current, operator, value)?;
+                }
+                if let LoxType::Instance(ref mut inst) = &mut *object.borrow_mut() {
+                    inst.set(name, value.clone());
                 }
+                Ok(value)
             }
         }
     }
 }
 
-impl stmt::Visitor<(), RuntimeException> for Interpreter {
-    fn visit_stmt(&mut self, stmt: &stmt::Stmt) -> Result<(), RuntimeException> {
+impl stmt::Visitor<(), ControlFlow> for Interpreter {
+    fn visit_stmt(&mut self, stmt: &stmt::Stmt) -> Result<(), ControlFlow> {
         match stmt {
             stmt::Stmt::Expression { expression } => {
                 self.evaluate(expression)?;
@@ -326,9 +1252,10 @@ impl stmt::Visitor<(), RuntimeException> for Interpreter {
                 condition,
                 then_branch,
                 else_branch,
+                ..
             } => {
                 let condition = self.evaluate(condition)?;
-                if Interpreter::is_truthy(&*condition.borrow()) {
+                if Interpreter::is_truthy(&condition.borrow()) {
                     self.execute(then_branch)?;
                 } else if let Some(else_branch) = else_branch {
                     self.execute(else_branch)?;
@@ -338,107 +1265,276 @@ impl stmt::Visitor<(), RuntimeException> for Interpreter {
             stmt::Stmt::While {
                 condition,
                 then_branch,
+                increment,
                 finally_branch,
+                fresh_bindings,
+                ..
             } => {
-                while Interpreter::is_truthy(&*self.evaluate(condition)?.borrow()) {
-                    if let Err(err) = self.execute(then_branch) {
-                        if err.token.token_type == TokenType::Break {
-                            break;
+                while Interpreter::is_truthy(&self.evaluate(condition)?.borrow()) {
+                    let result = if fresh_bindings.is_empty() {
+                        self.execute(then_branch)
+                    } else {
+                        self.execute_with_fresh_bindings(then_branch, fresh_bindings)
+                    };
+                    if let Err(err) = result {
+                        match err {
+                            // like python's while/else, finally is only for a loop that
+                            // ran until its condition was false
+                            ControlFlow::Break => return Ok(()),
+                            ControlFlow::Continue => {}
+                            _ => return Err(err),
                         }
                     }
+                    if let Some(increment) = increment {
+                        self.evaluate(increment)?;
+                    }
                 }
                 if let Some(finally_branch) = finally_branch {
                     self.execute(finally_branch)?;
                 }
                 Ok(())
             }
-            stmt::Stmt::Break { token } => Err(RuntimeException {
-                token: token.clone(),
-                message: "break".to_string(),
-                value: None,
-            }),
-            stmt::Stmt::Print { expression } => {
+            stmt::Stmt::ForEach {
+                keyword,
+                variable,
+                iterable,
+                body,
+            } => {
+                // a list is looped over as it was when the loop started
+                let items = match &*self.evaluate(iterable)?.borrow() {
+                    LoxType::List(l) => l.borrow().clone(),
+                    LoxType::Strang(s) => s
+                        .chars()
+                        .map(|c| Rc::new(RefCell::new(LoxType::Strang(c.to_string()))))
+                        .collect(),
+                    other => {
+                        return Err(RuntimeException::new(
+                            keyword.clone(),
+                            &format!("Can only loop over lists and strings, found {}", other),
+                        )
+                        .into())
+                    }
+                };
+
+                for item in items {
+                    let mut environment = Environment::new(Some(Rc::clone(&self.environment)));
+                    environment.define(variable.raw.clone(), item);
+                    let result = self.execute_block(
+                        std::slice::from_ref(body.as_ref()),
+                        Rc::new(RefCell::new(environment)),
+                    );
+                    if let Err(err) = result {
+                        match err {
+                            ControlFlow::Break => break,
+                            ControlFlow::Continue => {}
+                            _ => return Err(err),
+                        }
+                    }
+                }
+                Ok(())
+            }
+            stmt::Stmt::Defer {
+                keyword,
+                expression,
+            } => match self.deferred.last_mut() {
+                Some(deferred) => {
+                    deferred.push(expression.clone());
+                    Ok(())
+                }
+                None => Err(RuntimeException::new(
+                    keyword.clone(),
+                    "Can only defer inside a block",
+                )
+                .into()),
+            },
+            stmt::Stmt::Break { .. } => Err(ControlFlow::Break),
+            stmt::Stmt::Continue { .. } => Err(ControlFlow::Continue),
+            stmt::Stmt::MultiAssign { targets, values, .. } => {
+                let values = values
+                    .iter()
+                    .map(|value| self.evaluate(value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for (target, value) in targets.iter().zip(values) {
+                    self.assign_to(target, value)?;
+                }
+                Ok(())
+            }
+            stmt::Stmt::Print { expression, .. } => {
                 let val = self.evaluate(expression)?;
-                println!("{}", &*val.borrow().to_string());
+                self.print_value(&val.borrow());
                 Ok(())
             }
-            stmt::Stmt::Var { name, initializer } => {
+            stmt::Stmt::Assert {
+                keyword,
+                condition,
+                message,
+            } => {
+                if Interpreter::is_truthy(&self.evaluate(condition)?.borrow()) {
+                    return Ok(());
+                }
+
+                // show the condition as source, so it's clear what failed
+                let Ok(source) = expr::Visitor::visit_expr(&mut AstPrinter {}, condition);
+                let mut error = format!("assertion failed: {}", source);
+                if let Some(message) = message {
+                    error = format!("{}: {}", error, self.evaluate(message)?.borrow());
+                }
+                Err(RuntimeException::new(keyword.clone(), &error).into())
+            }
+            stmt::Stmt::Var {
+                name,
+                initializer,
+                is_const,
+            } => {
                 let mut val = Rc::new(RefCell::new(LoxType::Nil));
                 if let Some(init) = initializer {
                     val = self.evaluate(init)?;
+                    val = self.value_to_store(val);
                 }
 
-                self.environment.borrow_mut().define(name.raw.clone(), val);
+                if *is_const {
+                    self.environment.borrow_mut().define_const(name.raw.clone(), val);
+                } else {
+                    self.environment.borrow_mut().define(name.raw.clone(), val);
+                }
                 Ok(())
             }
             stmt::Stmt::Function {
                 name,
                 parameters,
                 body,
+                pure,
             } => {
-                let function = LoxFunction::new(
+                let mut function = LoxFunction::new(
                     name.clone(),
                     parameters.to_vec(),
                     body.to_vec(),
                     Rc::clone(&self.environment),
                 );
-                self.environment
-                    .borrow_mut()
-                    .define(name.raw.clone(), Rc::new(RefCell::new(LoxType::Function(Rc::new(function)))));
+                if *pure {
+                    function = function.memoized();
+                }
+                let function = Rc::new(RefCell::new(LoxType::Function(Rc::new(function))));
+                if let Some(heap_stats) = &mut self.heap_stats {
+                    heap_stats.track(&function);
+                }
+                self.environment.borrow_mut().define(name.raw.clone(), function);
                 Ok(())
             }
-            stmt::Stmt::Return {
-                token,
-                return_value,
-            } => {
-                let rv: Rc<RefCell<LoxType>>;
-                if let Some(val) = return_value {
-                    rv = self.evaluate(val)?;
-                } else {
-                    rv = Rc::new(RefCell::new(LoxType::Nil));
-                }
-                Err(RuntimeException {
-                    token: token.clone(),
-                    message: "return".to_string(),
-                    value: Some(rv),
-                })
+            stmt::Stmt::Return { return_value, .. } => {
+                let value = match return_value {
+                    Some(value) => Some(self.evaluate(value)?),
+                    None => None,
+                };
+                Err(ControlFlow::Return(value))
             }
-            stmt::Stmt::Block { statements } => {
+            stmt::Stmt::Block { statements, .. } => {
                 let block_env = Environment::new(Some(Rc::clone(&self.environment)));
-                self.execute_block(&statements, Rc::new(RefCell::new(block_env)))?;
+                self.execute_block(statements, Rc::new(RefCell::new(block_env)))?;
                 Ok(())
             }
-            stmt::Stmt::Class { name, .. } => {
+            stmt::Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
+                let superclass = match superclass {
+                    Some(expr) => match &*self.evaluate(expr)?.borrow() {
+                        LoxType::Class(c) => Some(Rc::new(c.clone())),
+                        _ => {
+                            let token = match expr {
+                                expr::Expr::Variable { name } => name.clone(),
+                                _ => name.clone(),
+                            };
+                            return Err(RuntimeException::new(token, "Superclass must be a class.").into());
+                        }
+                    },
+                    None => None,
+                };
+
                 self.environment
                     .borrow_mut()
                     .define(name.raw.to_string(), Rc::new(RefCell::new(LoxType::Nil)));
-                let class_ = Rc::new(RefCell::new(LoxType::Class(LoxClass::new(name.raw.to_string()))));
-                self.environment.borrow_mut().assign(&name, class_)?;
+
+                // methods of a subclass close over an extra environment holding `super`
+                let enclosing = Rc::clone(&self.environment);
+                if let Some(ref superclass) = superclass {
+                    let mut environment = Environment::new(Some(Rc::clone(&enclosing)));
+                    environment.define(
+                        "super".to_string(),
+                        Rc::new(RefCell::new(LoxType::Class((**superclass).clone()))),
+                    );
+                    self.environment = Rc::new(RefCell::new(environment));
+                }
+
+                let mut class_methods = HashMap::new();
+                for method in methods {
+                    if let stmt::Stmt::Function {
+                        name,
+                        parameters,
+                        body,
+                        ..
+                    } = method
+                    {
+                        let mut function = LoxFunction::new(
+                            name.clone(),
+                            parameters.to_vec(),
+                            body.to_vec(),
+                            Rc::clone(&self.environment),
+                        );
+                        if name.raw == "init" {
+                            function = function.initializer();
+                        }
+                        class_methods.insert(name.raw.clone(), Rc::new(function));
+                    }
+                }
+
+                self.environment = enclosing;
+
+                let class_ = Rc::new(RefCell::new(LoxType::Class(LoxClass::new(
+                    name.raw.to_string(),
+                    superclass,
+                    class_methods,
+                ))));
+                self.environment.borrow_mut().assign(name, class_)?;
                 Ok(())
             }
         }
     }
 }
 
+// how running statements can stop before the end. Only Error is a failure, the others
+// carry a break or continue out to their loop and a return out to its function
+#[derive(Debug)]
+pub enum ControlFlow {
+    Break,
+    Continue,
+    Return(Option<Rc<RefCell<LoxType>>>),
+    Error(RuntimeException),
+}
+
+impl From<RuntimeException> for ControlFlow {
+    fn from(err: RuntimeException) -> Self {
+        ControlFlow::Error(err)
+    }
+}
+
 #[derive(Debug)]
 pub struct RuntimeException {
     pub token: Token,
     pub message: String,
-    pub value: Option<Rc<RefCell<LoxType>>>,
+    // the calls the error was raised inside, innermost first. Only kept with --backtrace
+    pub backtrace: Option<Vec<String>>,
 }
 
 impl RuntimeException {
-    // alerts lox of runtime error and returns the error
-    pub fn report(token: Token, message: &str) -> Self {
-        println!(
-            "{} caused by {:?} at {:?}:{:?}",
-            message, token.token_type, token.line, token.column
-        );
-        lox::report_runtime_error();
+    // errors are reported by the interpreter once they escape to the top level,
+    // so they can still be caught on the way up
+    pub fn new(token: Token, message: &str) -> Self {
         Self {
             token,
             message: message.to_string(),
-            value: None,
+            backtrace: None,
         }
     }
 }
@@ -1,4 +1,9 @@
-use crate::{common::{*, self}, lexer_error, lox, token};
+use crate::{
+    common::{self, *},
+    lexer_error,
+    lox::{Diagnostic, ErrorReporter, Phase},
+    token,
+};
 use thiserror::Error;
 
 pub struct Lexer<'a> {
@@ -6,15 +11,17 @@ pub struct Lexer<'a> {
     tokens: Vec<Token>,
     line: u32,
     column: u32,
+    reporter: &'a ErrorReporter,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(source: &'a str) -> Self {
+    pub fn new(source: &'a str, reporter: &'a ErrorReporter) -> Self {
         Self {
             source: source.chars().peekable(),
             tokens: vec![],
             line: 1,
             column: 1,
+            reporter,
         }
     }
 
@@ -22,7 +29,7 @@ impl<'a> Lexer<'a> {
         if let Some(next) = self.source.peek() {
             return *next == want;
         }
-        return false;
+        false
     }
 
     fn skip_line_comment(&mut self) {
@@ -38,7 +45,7 @@ impl<'a> Lexer<'a> {
         loop {
             match self.consume_char() {
                 None => break,
-                Some(c) if c == '*' => {
+                Some('*') => {
                     match self.source.peek() {
                         None => {}
                         Some(c) if *c == '/' => {
@@ -81,7 +88,7 @@ impl<'a> Lexer<'a> {
                 None => {
                     return Err(self.error(LexerErrorKind::UnclosedStringLiteral { literal: buf }))
                 }
-                Some(c) if c == '"' => return Ok(token!(Strang, buf, (self.line, self.column))),
+                Some('"') => return Ok(token!(Strang, buf, (self.line, self.column))),
                 Some(c) => buf.push(c),
             }
         }
@@ -90,13 +97,14 @@ impl<'a> Lexer<'a> {
     fn parse_num(&mut self, start: char) -> Result<Token, LexerError> {
         let mut buf = String::from(start);
         let mut seen_dp = false;
+        let mut seen_exp = false;
 
         loop {
             match self.source.peek() {
                 None => return Ok(token!(Number, buf, (self.line, self.column))),
                 Some(c) if *c == '.' => {
-                    if seen_dp {
-                        // can't have two decimal points
+                    if seen_dp || seen_exp {
+                        // can't have two decimal points, or a decimal point in the exponent
                         // push for error
                         buf.push(*c);
                         return Err(self.error(LexerErrorKind::InvalidNumberLiteral {
@@ -114,10 +122,12 @@ impl<'a> Lexer<'a> {
                                     symbol: '.',
                                 }));
                             }
-                            Some(c) if !c.is_digit(10) => {
+                            Some(c) if !c.is_ascii_digit() => {
+                                // a separator can't directly follow the decimal point either
+                                let symbol = if *c == '_' { '_' } else { '.' };
                                 let err = self.error(LexerErrorKind::InvalidNumberLiteral {
                                     literal: buf,
-                                    symbol: '.',
+                                    symbol,
                                 });
                                 return Err(err);
                             }
@@ -125,22 +135,59 @@ impl<'a> Lexer<'a> {
                         }
                     }
                 }
+                Some(c) if (*c == 'e' || *c == 'E') && !seen_exp => {
+                    seen_exp = true;
+                    let exp = self.consume_char().unwrap();
+                    buf.push(exp);
+                    // optional sign
+                    if let Some(sign) = self.source.peek() {
+                        if *sign == '+' || *sign == '-' {
+                            buf.push(self.consume_char().unwrap());
+                        }
+                    }
+                    // exponent must have at least one digit
+                    match self.source.peek() {
+                        Some(c) if c.is_ascii_digit() => buf.push(self.consume_char().unwrap()),
+                        Some('_') => {
+                            return Err(self.error(LexerErrorKind::InvalidNumberLiteral {
+                                literal: buf,
+                                symbol: '_',
+                            }));
+                        }
+                        _ => {
+                            return Err(self.error(LexerErrorKind::InvalidNumberLiteral {
+                                literal: buf,
+                                symbol: exp,
+                            }));
+                        }
+                    }
+                }
+                Some('_') => {
+                    // digit separator, only allowed between two digits
+                    // and stripped from the literal
+                    self.consume_char();
+                    match self.source.peek() {
+                        Some(c) if c.is_ascii_digit() => {}
+                        _ => {
+                            buf.push('_');
+                            return Err(self.error(LexerErrorKind::InvalidNumberLiteral {
+                                literal: buf,
+                                symbol: '_',
+                            }));
+                        }
+                    }
+                }
                 Some(c) if c.is_whitespace() || common::is_punctuation(c) => break,
+                Some(c) if c.is_ascii_digit() => buf.push(self.consume_char().unwrap()),
                 Some(c) if c.is_ascii_alphabetic() => {
-                    let kind = LexerErrorKind::InvalidNumberLiteral {
+                    let symbol = *c;
+                    return Err(self.error(LexerErrorKind::InvalidNumberLiteral {
                         literal: buf,
-                        symbol: *c,
-                    };
-                    println!(
-                        "line {} column {}: {}",
-                        self.line,
-                        self.column,
-                        kind.to_string()
-                    );
-                    lox::report_error();
-                    return Err(lexer_error!(kind, (self.line, self.column)));
+                        symbol,
+                    }));
                 }
-                Some(_) => buf.push(self.consume_char().unwrap()),
+                // an operator directly after the literal, e.g. 1+2
+                Some(_) => break,
             }
         }
         Ok(token!(Number, buf, (self.line, self.column)))
@@ -160,19 +207,32 @@ impl<'a> Lexer<'a> {
         // check if it's a keyword
         // it is a keyword
         if let Some(token_type) = KEYWORDS.get(&buf).cloned() {
-            return Ok(Token {
+            Ok(Token {
                 token_type,
                 raw: buf,
                 line: self.line,
                 column: self.column,
-            });
+                start_line: self.line,
+                start_column: self.column,
+            })
         } else {
             // it's a plain ol' identifier
-            return Ok(token!(Identifier, buf, (self.line, self.column)));
+            Ok(token!(Identifier, buf, (self.line, self.column)))
         }
     }
 
     fn lex_token(&mut self) {
+        let (start_line, start_column) = (self.line, self.column);
+        let lexed = self.tokens.len();
+        self.lex_next();
+        // tokens are made once they've been read, so only know where they end
+        for token in &mut self.tokens[lexed..] {
+            token.start_line = start_line;
+            token.start_column = start_column;
+        }
+    }
+
+    fn lex_next(&mut self) {
         if let Some(c) = self.consume_char() {
             match c {
                 '(' => self
@@ -187,22 +247,69 @@ impl<'a> Lexer<'a> {
                 '}' => self
                     .tokens
                     .push(token!(RightBrace, "}", (self.line, self.column))),
-                ',' => self
-                    .tokens
-                    .push(token!(Comma, ",", (self.line, self.column))),
-                '.' => self.tokens.push(token!(Dot, ".", (self.line, self.column))),
-                '-' => self
+                '[' => self
                     .tokens
-                    .push(token!(Minus, "-", (self.line, self.column))),
-                '+' => self
+                    .push(token!(LeftBracket, "[", (self.line, self.column))),
+                ']' => self
                     .tokens
-                    .push(token!(Plus, "+", (self.line, self.column))),
-                '*' => self
+                    .push(token!(RightBracket, "]", (self.line, self.column))),
+                ',' => self
                     .tokens
-                    .push(token!(Star, "*", (self.line, self.column))),
+                    .push(token!(Comma, ",", (self.line, self.column))),
+                '.' => {
+                    if self.match_next('.') {
+                        // the only token starting with two dots is the spread operator
+                        self.consume_char();
+                        if self.match_next('.') {
+                            self.consume_char();
+                            self.tokens
+                                .push(token!(Ellipsis, "...", (self.line, self.column)));
+                        } else {
+                            self.error(LexerErrorKind::UnrecognisedSymbol { symbol: '.' });
+                        }
+                    } else {
+                        self.tokens.push(token!(Dot, ".", (self.line, self.column)));
+                    }
+                }
+                '-' => {
+                    if self.match_next('=') {
+                        self.consume_char();
+                        self.tokens
+                            .push(token!(MinusEqual, "-=", (self.line, self.column)));
+                    } else {
+                        self.tokens
+                            .push(token!(Minus, "-", (self.line, self.column)));
+                    }
+                }
+                '+' => {
+                    if self.match_next('=') {
+                        self.consume_char();
+                        self.tokens
+                            .push(token!(PlusEqual, "+=", (self.line, self.column)));
+                    } else {
+                        self.tokens
+                            .push(token!(Plus, "+", (self.line, self.column)));
+                    }
+                }
+                '*' => {
+                    if self.match_next('=') {
+                        self.consume_char();
+                        self.tokens
+                            .push(token!(StarEqual, "*=", (self.line, self.column)));
+                    } else {
+                        self.tokens
+                            .push(token!(Star, "*", (self.line, self.column)));
+                    }
+                }
                 ';' => self
                     .tokens
                     .push(token!(SemiColon, ";", (self.line, self.column))),
+                '?' => self
+                    .tokens
+                    .push(token!(Question, "?", (self.line, self.column))),
+                ':' => self
+                    .tokens
+                    .push(token!(Colon, ":", (self.line, self.column))),
                 '!' => {
                     if self.match_next('=') {
                         self.consume_char();
@@ -213,8 +320,21 @@ impl<'a> Lexer<'a> {
                             .push(token!(Bang, "!", (self.line, self.column)));
                     }
                 }
+                '&' => self
+                    .tokens
+                    .push(token!(Ampersand, "&", (self.line, self.column))),
+                '|' => self
+                    .tokens
+                    .push(token!(Pipe, "|", (self.line, self.column))),
+                '^' => self
+                    .tokens
+                    .push(token!(Caret, "^", (self.line, self.column))),
                 '<' => {
-                    if self.match_next('=') {
+                    if self.match_next('<') {
+                        self.consume_char();
+                        self.tokens
+                            .push(token!(LessLess, "<<", (self.line, self.column)));
+                    } else if self.match_next('=') {
                         self.consume_char();
                         self.tokens
                             .push(token!(LessEqual, "<=", (self.line, self.column)));
@@ -224,7 +344,11 @@ impl<'a> Lexer<'a> {
                     }
                 }
                 '>' => {
-                    if self.match_next('=') {
+                    if self.match_next('>') {
+                        self.consume_char();
+                        self.tokens
+                            .push(token!(GreaterGreater, ">>", (self.line, self.column)));
+                    } else if self.match_next('=') {
                         self.consume_char();
                         self.tokens
                             .push(token!(GreaterEqual, ">=", (self.line, self.column)));
@@ -250,37 +374,32 @@ impl<'a> Lexer<'a> {
                     } else if self.match_next('*') {
                         // it's a line comment
                         self.skip_block_comment();
+                    } else if self.match_next('=') {
+                        self.consume_char();
+                        self.tokens
+                            .push(token!(SlashEqual, "/=", (self.line, self.column)));
                     } else {
                         self.tokens
                             .push(token!(Slash, "/", (self.line, self.column)));
                     }
                 }
                 '"' => {
-                    let string_tok = self.parse_string();
-                    match string_tok {
-                        Ok(tok) => self.tokens.push(tok),
-                        Err(e) => {
-                            self.error(e.kind);
-                        }
+                    // errors have already been reported by the time they get here
+                    if let Ok(tok) = self.parse_string() {
+                        self.tokens.push(tok);
                     }
                 }
                 c if c.is_whitespace() => self.skip_whitespace(),
                 '0'..='9' => {
-                    let num_tok = self.parse_num(c);
-                    match num_tok {
-                        Ok(tok) => self.tokens.push(tok),
-                        Err(e) => {
-                            self.error(e.kind);
-                        }
+                    // errors have already been reported by the time they get here
+                    if let Ok(tok) = self.parse_num(c) {
+                        self.tokens.push(tok);
                     }
                 }
                 c if c.is_ascii_alphabetic() || c == '_' => {
-                    let ident_tok = self.parse_identifier(c);
-                    match ident_tok {
-                        Ok(tok) => self.tokens.push(tok),
-                        Err(e) => {
-                            self.error(e.kind);
-                        }
+                    // errors have already been reported by the time they get here
+                    if let Ok(tok) = self.parse_identifier(c) {
+                        self.tokens.push(tok);
                     }
                 }
 
@@ -292,17 +411,16 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn is_at_end(&mut self) -> bool {
-        self.source.peek() == None
+        self.source.peek().is_none()
     }
 
     fn error(&self, kind: LexerErrorKind) -> LexerError {
-        println!(
-            "lexer: line {} column {}: {}",
+        self.reporter.report_error(Diagnostic::new(
+            Phase::Lex,
+            kind.to_string(),
             self.line,
             self.column,
-            kind.to_string()
-        );
-        lox::report_error();
+        ));
         lexer_error!(kind, (self.line, self.column))
     }
 
@@ -317,6 +435,36 @@ impl<'a> Lexer<'a> {
 
         self.tokens
     }
+
+    // as collect_tokens, but keeping where each token starts, for tooling like editors
+    pub fn collect_token_spans(mut self) -> Vec<TokenSpan> {
+        let mut spans = vec![];
+        while !self.is_at_end() {
+            let (start_line, start_column) = (self.line, self.column);
+            let lexed = self.tokens.len();
+            self.lex_token();
+            spans.extend(self.tokens.drain(lexed..).map(|token| TokenSpan {
+                token,
+                start_line,
+                start_column,
+            }));
+        }
+
+        spans.push(TokenSpan {
+            token: token!(EOF, "", (self.line, self.column)),
+            start_line: self.line,
+            start_column: self.column,
+        });
+        spans
+    }
+}
+
+// a token along with where it starts, the token itself only keeps where it ends
+#[derive(Debug, Clone)]
+pub struct TokenSpan {
+    pub token: Token,
+    pub start_line: u32,
+    pub start_column: u32,
 }
 
 #[derive(Debug)]
@@ -0,0 +1,17 @@
+pub mod ast_graphviz;
+pub mod ast_json;
+pub mod ast_printer;
+pub mod bytecode;
+pub mod common;
+pub mod debugger;
+pub mod environment;
+pub mod expr;
+pub mod heap_stats;
+pub mod interpreter;
+pub mod lexer;
+pub mod lox;
+pub mod parser;
+pub mod stmt;
+pub mod native_functions;
+pub mod resolver;
+pub mod token_json;
@@ -1,27 +1,262 @@
-use crate::{interpreter::Interpreter, lexer::Lexer, parser::Parser, resolver::Resolver};
-use std::{io::Write, cell::RefCell, rc::Rc};
+use crate::{
+    ast_graphviz::AstGraphviz, ast_json, bytecode, common::{Token, LOX_MAX_ARGUMENT_COUNT}, debugger::Debugger,
+    interpreter::{Interpreter, SharedStdin},
+    lexer::Lexer, parser::Parser, resolver::Resolver, stmt::Stmt, token_json,
+};
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Display,
+    io::Write,
+    rc::Rc,
+};
 
-static mut HAD_ERROR: bool = false;
-static mut HAD_RUNTIME_ERROR: bool = false;
-
-pub fn run_file(file_path: &str) {
-    let file_data = match std::fs::read_to_string(file_path) {
+fn read_file(file_path: &str) -> String {
+    match std::fs::read_to_string(file_path) {
         Ok(data) => data,
         Err(e) => {
             println!("{}", e);
             std::process::exit(64);
         }
-    };
+    }
+}
+
+// settings from the command line that apply to every piece of source that gets run
+#[derive(Debug, Clone, Copy)]
+pub struct RunOptions {
+    // how many lines either side of an error to print alongside it, if any
+    pub context: Option<usize>,
+    // most arguments a call, or parameters a function, may have. None for no limit
+    pub max_args: Option<usize>,
+    // stops natives reaching outside the interpreter, e.g. env
+    pub sandbox: bool,
+    // pause before each statement, taking debugger commands from stdin
+    pub step: bool,
+    // when running several files, carry on with the rest after one fails
+    pub keep_going: bool,
+    // stop scripts redefining or assigning to the built-in natives, e.g. clock
+    pub protect_builtins: bool,
+    // once the program's done, print how many objects it left alive
+    pub heap_stats: bool,
+    // print the chain of calls leading to a runtime error
+    pub backtrace: bool,
+    // copy lists and maps on assignment and when passed to functions, instead of sharing them
+    pub value_semantics: bool,
+    // warn about function parameters that are never used, as well as locals
+    pub warn_unused: bool,
+    // numbers this close together are ==, None for exact comparison
+    pub epsilon: Option<f64>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            context: None,
+            max_args: Some(LOX_MAX_ARGUMENT_COUNT),
+            sandbox: false,
+            step: false,
+            keep_going: false,
+            protect_builtins: false,
+            heap_stats: false,
+            backtrace: false,
+            value_semantics: false,
+            warn_unused: false,
+            epsilon: None,
+        }
+    }
+}
+
+pub fn run_file(file_path: &str, options: RunOptions) {
+    run_files(&[file_path.to_string()], options);
+}
+
+fn new_interpreter(options: &RunOptions) -> Rc<RefCell<Interpreter>> {
+    Rc::new(RefCell::new(configured_interpreter(options)))
+}
+
+fn configured_interpreter(options: &RunOptions) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_allow_io(!options.sandbox);
+    interpreter.set_protect_builtins(options.protect_builtins);
+    interpreter.set_backtrace(options.backtrace);
+    interpreter.set_value_semantics(options.value_semantics);
+    interpreter.set_float_epsilon(options.epsilon);
+    interpreter.set_max_args(options.max_args);
+    if options.heap_stats {
+        interpreter.enable_heap_stats();
+    }
+    interpreter
+}
+
+// once nothing more will run, e.g. reports what's still alive for --heap-stats
+fn finish(interpreter: Rc<RefCell<Interpreter>>) {
+    let live = interpreter.borrow_mut().finish_heap_stats();
+    if let Some(live) = live {
+        interpreter.borrow_mut().write_error(&live.to_string());
+    }
+}
+
+// runs each file in order against the same globals, so later files can use what earlier
+// ones defined. stops at the first file that fails unless keep_going is set
+pub fn run_files(file_paths: &[String], options: RunOptions) {
+    let interpreter = new_interpreter(&options);
+    run_each(file_paths, &interpreter, &options);
+    finish(interpreter);
+}
+
+// runs the files, then starts the repl with whatever they left in globals to poke at
+pub fn run_files_then_interactive(file_paths: &[String], options: RunOptions) {
+    let interpreter = new_interpreter(&options);
+    run_each(file_paths, &interpreter, &options);
+    repl(&interpreter, &options);
+    finish(interpreter);
+}
+
+fn run_each(file_paths: &[String], interpreter: &Rc<RefCell<Interpreter>>, options: &RunOptions) {
+    for file_path in file_paths {
+        let file_data = read_file(file_path);
+        if options.step {
+            let stdin = Box::new(SharedStdin::default());
+            interpreter
+                .borrow_mut()
+                .set_debugger(Some(Debugger::new(&file_data, stdin)));
+        }
+
+        if !run(&file_data, Rc::clone(interpreter), options) && !options.keep_going {
+            break;
+        }
+    }
+}
+
+// lexes and parses a file without running it, for tooling that only needs the tree
+pub fn parse_file(file_path: &str) -> Result<Vec<Stmt>, Vec<Diagnostic>> {
+    parse_source(&read_file(file_path))
+}
+
+pub fn parse_source(source: &str) -> Result<Vec<Stmt>, Vec<Diagnostic>> {
+    let reporter = ErrorReporter::new();
+
+    let tokens = Lexer::new(source, &reporter).collect_tokens();
+    if reporter.had_error() {
+        return Err(reporter.take_diagnostics());
+    }
 
-    run(&file_data, Rc::new(RefCell::new(Interpreter::new())));
+    let statements = Parser::new(tokens, &reporter).parse();
+    if reporter.had_error() {
+        return Err(reporter.take_diagnostics());
+    }
+    Ok(statements)
+}
+
+pub fn print_ast_graphviz(file_path: &str) {
+    match parse_file(file_path) {
+        Ok(statements) => print!("{}", AstGraphviz::new().render(&statements)),
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                println!("{}", diagnostic);
+            }
+        }
+    }
+}
+
+pub fn print_ast_json(file_path: &str) {
+    match parse_file(file_path) {
+        Ok(statements) => println!("{}", ast_json::ast_json(&statements)),
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                println!("{}", diagnostic);
+            }
+        }
+    }
+}
+
+pub fn print_bytecode(file_path: &str) {
+    match parse_file(file_path) {
+        Ok(statements) => {
+            for chunk in bytecode::compile(&statements) {
+                match chunk {
+                    Ok(chunk) => println!("{}", chunk.disassemble()),
+                    Err(message) => println!("{}\n", message),
+                }
+            }
+        }
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                println!("{}", diagnostic);
+            }
+        }
+    }
+}
+
+pub fn print_ast_fingerprint(file_path: &str) {
+    match parse_file(file_path) {
+        Ok(statements) => println!("{}", ast_json::ast_fingerprint(&statements)),
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                println!("{}", diagnostic);
+            }
+        }
+    }
 }
 
-pub fn run_interactive() {
-    let interpreter = Rc::new(RefCell::new(Interpreter::new()));
+pub fn print_tokens_json(file_path: &str) {
+    println!("{}", token_json::tokens_json(&read_file(file_path)));
+}
+
+pub fn run_interactive(options: RunOptions) {
+    let interpreter = new_interpreter(&options);
+    repl(&interpreter, &options);
+    finish(interpreter);
+}
+
+// lines the repl handles itself instead of running as lox
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReplCommand {
+    // start again with a fresh interpreter, forgetting every global
+    Reset,
+    // print the globals defined so far, leaving out the natives
+    Env,
+    Quit,
+}
+
+// no lox statement starts with a colon, so these can't be mistaken for source
+pub fn repl_command(line: &str) -> Option<ReplCommand> {
+    match line.trim() {
+        ":reset" => Some(ReplCommand::Reset),
+        ":env" => Some(ReplCommand::Env),
+        ":quit" | ":q" => Some(ReplCommand::Quit),
+        _ => None,
+    }
+}
+
+// each global a script defined as `name = value`, sorted by name
+fn globals_listing(interpreter: &Interpreter) -> Vec<String> {
+    let mut globals: Vec<_> = interpreter
+        .globals()
+        .borrow()
+        .variables()
+        .into_iter()
+        .filter(|(name, _)| !interpreter.is_builtin(name))
+        .collect();
+    globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+    globals
+        .into_iter()
+        .map(|(name, value)| format!("{} = {}", name, value.borrow()))
+        .collect()
+}
+
+fn write_globals(interpreter: &mut Interpreter) {
+    for line in globals_listing(interpreter) {
+        interpreter.write_line(&line);
+    }
+}
+
+fn repl(interpreter: &Rc<RefCell<Interpreter>>, options: &RunOptions) {
+    // keep output in step with the prompt
+    interpreter.borrow_mut().set_flush_on_print(true);
+    // lines of a statement that's still being typed, e.g. a function body
+    let mut buffer = String::new();
     loop {
-        unsafe { HAD_ERROR = false };
-        unsafe { HAD_RUNTIME_ERROR = false };
-        print!(":> ");
+        print!("{}", if buffer.is_empty() { ":> " } else { ".. " });
         std::io::stdout().flush().unwrap();
         let mut input = String::new();
         std::io::stdin()
@@ -32,39 +267,350 @@ pub fn run_interactive() {
             break;
         }
 
-        run(input.trim(), Rc::clone(&interpreter));
+        // commands work partway through a statement too, e.g. to quit out of one
+        match repl_command(&input) {
+            Some(ReplCommand::Reset) => {
+                interpreter.replace(configured_interpreter(options));
+                interpreter.borrow_mut().set_flush_on_print(true);
+                buffer.clear();
+                continue;
+            }
+            Some(ReplCommand::Env) => {
+                write_globals(&mut interpreter.borrow_mut());
+                continue;
+            }
+            Some(ReplCommand::Quit) => break,
+            None => {}
+        }
+
+        // a blank line gives up waiting for the rest, running what's there to show the error
+        let give_up = !buffer.is_empty() && input.trim().is_empty();
+        buffer.push_str(&input);
+        if !give_up && needs_more_input(&buffer, options) {
+            continue;
+        }
+        run_source(buffer.trim(), Rc::clone(interpreter), options, true);
+        buffer.clear();
     }
 }
 
-pub fn run(source: &str, interpreter: Rc<RefCell<Interpreter>>) {
-    let lexer = Lexer::new(source);
+// whether source only fails to parse because it stops partway through a statement
+fn needs_more_input(source: &str, options: &RunOptions) -> bool {
+    let reporter = ErrorReporter::new();
+    let tokens = Lexer::new(source, &reporter).collect_tokens();
+    if reporter.had_error() {
+        return false;
+    }
+    let mut parser = Parser::new(tokens, &reporter).with_max_args(options.max_args);
+    parser.parse();
+    parser.needs_more_input()
+}
+
+// gives back whether the source ran without any errors
+pub fn run(source: &str, interpreter: Rc<RefCell<Interpreter>>, options: &RunOptions) -> bool {
+    run_source(source, interpreter, options, false)
+}
+
+// with echo, source that's a single expression statement has its value printed, for the repl
+fn run_source(source: &str, interpreter: Rc<RefCell<Interpreter>>, options: &RunOptions, echo: bool) -> bool {
+    let reporter = ErrorReporter::new();
+    let statements = prepare(source, &interpreter, options, &reporter);
+    // warnings are written before running, so they don't trail the program's output
+    write_diagnostics(source, &interpreter, options, reporter.take_diagnostics());
+
+    if let Some(statements) = statements {
+        if echo {
+            interpreter.borrow_mut().interpret_echoing(&statements, &reporter);
+        } else {
+            interpreter.borrow_mut().interpret(&statements, &reporter);
+        }
+        write_diagnostics(source, &interpreter, options, reporter.take_diagnostics());
+    }
+    !reporter.had_error() && !reporter.had_runtime_error()
+}
+
+fn write_diagnostics(
+    source: &str,
+    interpreter: &Rc<RefCell<Interpreter>>,
+    options: &RunOptions,
+    diagnostics: Vec<Diagnostic>,
+) {
+    let mut interpreter = interpreter.borrow_mut();
+    for diagnostic in diagnostics {
+        interpreter.write_error(&diagnostic.to_string());
+        interpreter.write_error(&diagnostic.render_snippet(source, options.context.unwrap_or(0)));
+    }
+}
+
+// lexes, parses and resolves source, giving back the statements if it's ready to run.
+// anything found along the way is left on the reporter
+fn prepare(
+    source: &str,
+    interpreter: &Rc<RefCell<Interpreter>>,
+    options: &RunOptions,
+    reporter: &ErrorReporter,
+) -> Option<Vec<Stmt>> {
+    let lexer = Lexer::new(source, reporter);
     let tokens = lexer.collect_tokens();
 
-    if unsafe { HAD_ERROR } {
-        return;
+    if reporter.had_error() {
+        return None;
     }
 
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::new(tokens, reporter).with_max_args(options.max_args);
     let statements = parser.parse();
 
-    if unsafe { HAD_ERROR } {
-        return;
+    if reporter.had_error() {
+        return None;
     }
-    
-    let mut resolver = Resolver::new(Rc::clone(&interpreter));
+
+    let mut resolver = Resolver::new(Rc::clone(interpreter), reporter)
+        .with_unused_parameter_warnings(options.warn_unused);
     resolver.resolve(&statements);
 
-    if unsafe { HAD_ERROR } {
-        return;
+    if reporter.had_error() {
+        return None;
+    }
+    Some(statements)
+}
+
+// runs source without printing any errors, handing them back to the caller instead
+pub fn run_collecting(source: &str, interpreter: Rc<RefCell<Interpreter>>) -> Result<(), Vec<Diagnostic>> {
+    run_collecting_with(source, interpreter, &RunOptions::default()).map(|_| ())
+}
+
+// as run_collecting, under the given options. A successful run hands back any warnings,
+// a failed one its errors along with them
+pub fn run_collecting_with(
+    source: &str,
+    interpreter: Rc<RefCell<Interpreter>>,
+    options: &RunOptions,
+) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+    let reporter = ErrorReporter::new();
+
+    let statements = match prepare(source, &interpreter, options, &reporter) {
+        Some(statements) => statements,
+        None => return Err(reporter.take_diagnostics()),
+    };
+
+    interpreter.borrow_mut().interpret(&statements, &reporter);
+
+    if reporter.had_runtime_error() {
+        return Err(reporter.take_diagnostics());
     }
+    Ok(reporter.take_diagnostics())
+}
 
-    interpreter.borrow_mut().interpret(&statements);
+// tracks the errors raised while running one piece of source, shared by every phase
+#[derive(Default)]
+pub struct ErrorReporter {
+    had_error: Cell<bool>,
+    had_runtime_error: Cell<bool>,
+    diagnostics: RefCell<Vec<Diagnostic>>,
 }
 
-pub fn report_error() {
-    unsafe { HAD_ERROR = true };
+impl ErrorReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn report_error(&self, diagnostic: Diagnostic) {
+        self.had_error.set(true);
+        self.diagnostics.borrow_mut().push(diagnostic);
+    }
+
+    // warnings don't stop the source from running
+    pub fn report_warning(&self, diagnostic: Diagnostic) {
+        self.diagnostics.borrow_mut().push(diagnostic);
+    }
+
+    pub fn report_runtime_error(&self, diagnostic: Diagnostic) {
+        self.had_runtime_error.set(true);
+        self.diagnostics.borrow_mut().push(diagnostic);
+    }
+
+    pub fn had_error(&self) -> bool {
+        self.had_error.get()
+    }
+
+    pub fn had_runtime_error(&self) -> bool {
+        self.had_runtime_error.get()
+    }
+
+    pub fn take_diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics.take()
+    }
 }
 
-pub fn report_runtime_error() {
-    unsafe { HAD_RUNTIME_ERROR = true };
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Lex,
+    Parse,
+    Resolve,
+    Runtime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub phase: Phase,
+    pub severity: Severity,
+    pub message: String,
+    pub line: u32,
+    // just past the end of the offending token
+    pub column: u32,
+    // where the caret goes, the first character of the offending token. The message
+    // reports this column too, so it matches the caret
+    pub start_column: u32,
+    // extra lines printed after the message, e.g. a backtrace
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(phase: Phase, message: String, line: u32, column: u32) -> Self {
+        Self {
+            phase,
+            severity: Severity::Error,
+            message,
+            line,
+            column,
+            // without a token to go by, the character just before the column
+            start_column: column.saturating_sub(1).max(1),
+            notes: vec![],
+        }
+    }
+
+    // puts the caret under the start of token. One that began on an earlier line, like a
+    // string spanning several, is pointed at from the start of the line it ends on
+    pub fn starting_at(self, token: &Token) -> Self {
+        let start_column = if token.start_line == token.line {
+            token.start_column
+        } else {
+            1
+        };
+        Self { start_column, ..self }
+    }
+
+    pub fn with_notes(self, notes: Vec<String>) -> Self {
+        Self { notes, ..self }
+    }
+
+    pub fn warning(phase: Phase, message: String, line: u32, column: u32) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::new(phase, message, line, column)
+        }
+    }
+
+    // the offending line and `context` lines either side of it, with line numbers in a gutter
+    // and a caret under the start of what's wrong, e.g.
+    //   1 | var a = 1;
+    // > 2 | print b;
+    //     |       ^
+    //   3 | print a;
+    pub fn render_snippet(&self, source: &str, context: usize) -> String {
+        let lines: Vec<&str> = source.lines().collect();
+        if lines.is_empty() {
+            return String::new();
+        }
+
+        // errors at end of file can sit one past the last line
+        let error_line = (self.line.max(1) as usize).min(lines.len());
+        let first = error_line.saturating_sub(context).max(1);
+        let last = (error_line + context).min(lines.len());
+        let gutter_width = last.to_string().len();
+
+        let mut snippet = vec![];
+        for n in first..=last {
+            let line = lines[n - 1];
+            if n != error_line {
+                snippet.push(format!("  {:>width$} | {}", n, line, width = gutter_width));
+                continue;
+            }
+            snippet.push(format!("> {:>width$} | {}", n, line, width = gutter_width));
+            // an error at end of file past the last line points just after that line's end
+            let offset = if self.line as usize > lines.len() {
+                line.chars().count()
+            } else {
+                (self.start_column as usize).saturating_sub(1)
+            };
+            // tabs are kept so the caret lines up however wide the terminal shows them
+            let indent: String = line
+                .chars()
+                .chain(std::iter::repeat(' '))
+                .take(offset)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            snippet.push(format!("  {:>width$} | {}^", "", indent, width = gutter_width));
+        }
+        snippet.join("\n")
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.severity == Severity::Warning {
+            write!(f, "warning: ")?;
+        }
+        match self.phase {
+            Phase::Lex => write!(
+                f,
+                "lexer: line {} column {}: {}",
+                self.line, self.start_column, self.message
+            ),
+            Phase::Parse => write!(
+                f,
+                "parser: {}, at line {} column {}",
+                self.message, self.line, self.start_column
+            ),
+            Phase::Resolve => write!(
+                f,
+                "Resolver: {} at line {} column {}",
+                self.message, self.line, self.start_column
+            ),
+            Phase::Runtime => write!(f, "{} at {}:{}", self.message, self.line, self.start_column),
+        }?;
+        for note in &self.notes {
+            write!(f, "\n{}", note)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repl_commands_are_recognised() {
+        assert_eq!(repl_command(":reset\n"), Some(ReplCommand::Reset));
+        assert_eq!(repl_command("  :env  "), Some(ReplCommand::Env));
+        assert_eq!(repl_command(":quit\n"), Some(ReplCommand::Quit));
+        assert_eq!(repl_command(":q"), Some(ReplCommand::Quit));
+    }
+
+    #[test]
+    fn anything_else_is_lox() {
+        assert_eq!(repl_command("print 1;\n"), None);
+        assert_eq!(repl_command(":exit"), None);
+        assert_eq!(repl_command(":env extra"), None);
+        assert_eq!(repl_command(""), None);
+    }
+
+    #[test]
+    fn globals_listing_leaves_out_natives() {
+        let interpreter = Rc::new(RefCell::new(Interpreter::with_output(Box::new(std::io::sink()))));
+        assert!(run("var b = [1, 2];\nvar a = \"x\";\nfunct f() {}", Rc::clone(&interpreter), &RunOptions::default()));
+
+        let listing = globals_listing(&interpreter.borrow());
+        assert_eq!(listing.len(), 3);
+        assert_eq!(listing[0], "a = x");
+        assert_eq!(listing[1], "b = [1, 2]");
+        assert!(listing[2].starts_with("f = "));
+    }
 }
@@ -1,33 +1,125 @@
-pub mod ast_printer;
-pub mod common;
-pub mod environment;
-pub mod expr;
-pub mod interpreter;
-pub mod lexer;
-pub mod lox;
-pub mod parser;
-pub mod stmt;
-pub mod native_functions;
-pub mod resolver;
-
 use clap::Parser;
+use ::lox::{common::LOX_MAX_ARGUMENT_COUNT, lox};
 
 #[derive(Parser, Debug)]
 #[clap(author="ObiWanWheeler", version="0.0.1", about="An interpreter for the Lox language specification, found at https://github.com/munificent/craftinginterpreters", long_about = None)]
 struct Args {
-    #[clap(short, long)]
-    file_path: Option<String>,
+    /// a file to run, can be given more than once to run several in order with shared globals
+    #[clap(short = 'f', long = "file-path", alias = "file")]
+    file_paths: Vec<String>,
+
+    /// after running the files, start the repl with their globals still in scope
+    #[clap(short = 'i', long, requires = "file-paths")]
+    interactive: bool,
+
+    /// print the parse tree of each file as graphviz DOT instead of running it
+    #[clap(long, requires = "file-paths")]
+    ast_graphviz: bool,
+
+    /// print the parse tree of each file as JSON instead of running it, for editor tooling
+    #[clap(long, requires = "file-paths")]
+    emit_ast_json: bool,
+
+    /// print the bytecode each function in each file lowers to instead of running it
+    #[clap(long, requires = "file-paths")]
+    dump_bytecode: bool,
+
+    /// print a hash of each file's parse tree that ignores positions, for snapshot tests
+    #[clap(long, requires = "file-paths")]
+    ast_fingerprint: bool,
+
+    /// print the tokens of each file as JSON instead of running it, for editor highlighting
+    #[clap(long, requires = "file-paths")]
+    emit_tokens_json: bool,
+
+    /// print N lines either side of each error's line, not just the line (2 if N is left out)
+    #[clap(long, value_name = "N", min_values = 0, default_missing_value = "2")]
+    context: Option<usize>,
+
+    /// most arguments a call, or parameters a function, may have. 0 for no limit
+    #[clap(long, value_name = "N", default_value_t = LOX_MAX_ARGUMENT_COUNT)]
+    max_args: usize,
+
+    /// stop scripts reaching outside the interpreter, e.g. reading environment variables
+    #[clap(long)]
+    sandbox: bool,
+
+    /// pause before each statement, enter steps, env shows variables, continue runs on
+    #[clap(long, requires = "file-paths")]
+    step: bool,
+
+    /// when running several files, carry on with the rest after one fails
+    #[clap(long)]
+    keep_going: bool,
+
+    /// make it an error to redefine or assign to a built-in function like clock
+    #[clap(long)]
+    protect_builtins: bool,
+
+    /// once the program's done, print how many lists, maps, instances and closures are
+    /// still alive, which points to reference cycles
+    #[clap(long)]
+    heap_stats: bool,
+
+    /// print the chain of calls leading up to a runtime error
+    #[clap(long)]
+    backtrace: bool,
+
+    /// copy lists and maps when they're assigned or passed to a function, rather than
+    /// sharing them between names
+    #[clap(long)]
+    value_semantics: bool,
+
+    /// warn about function parameters that are never used, unless their name starts with _
+    #[clap(long)]
+    warn_unused: bool,
+
+    /// make == and != treat numbers within E of each other as equal, e.g. 1e-9
+    #[clap(long, value_name = "E")]
+    epsilon: Option<f64>,
 }
 
 fn main() {
     let args = Args::parse();
+    let options = lox::RunOptions {
+        context: args.context,
+        max_args: (args.max_args != 0).then_some(args.max_args),
+        sandbox: args.sandbox,
+        step: args.step,
+        keep_going: args.keep_going,
+        protect_builtins: args.protect_builtins,
+        heap_stats: args.heap_stats,
+        backtrace: args.backtrace,
+        value_semantics: args.value_semantics,
+        warn_unused: args.warn_unused,
+        epsilon: args.epsilon,
+    };
 
-    match args.file_path {
-        Some(fp) => {
-            lox::run_file(&fp);
+    if args.file_paths.is_empty() {
+        lox::run_interactive(options);
+    } else if args.emit_tokens_json {
+        for fp in &args.file_paths {
+            lox::print_tokens_json(fp);
+        }
+    } else if args.dump_bytecode {
+        for fp in &args.file_paths {
+            lox::print_bytecode(fp);
+        }
+    } else if args.ast_fingerprint {
+        for fp in &args.file_paths {
+            lox::print_ast_fingerprint(fp);
+        }
+    } else if args.emit_ast_json {
+        for fp in &args.file_paths {
+            lox::print_ast_json(fp);
         }
-        None => {
-            lox::run_interactive();
+    } else if args.ast_graphviz {
+        for fp in &args.file_paths {
+            lox::print_ast_graphviz(fp);
         }
+    } else if args.interactive {
+        lox::run_files_then_interactive(&args.file_paths, options);
+    } else {
+        lox::run_files(&args.file_paths, options);
     }
 }
@@ -1,10 +1,25 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
 
 use crate::{
-    common::{LoxCallable, LoxType},
+    common::{LazyState, LoxCallable, LoxFunction, LoxType, Token, TokenType},
+    expr::Expr,
     interpreter::RuntimeException,
+    lexer::Lexer,
+    lox::ErrorReporter,
+    parser::Parser,
 };
 
+fn since_epoch() -> std::time::Duration {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap()
+}
+
+// seconds since the unix epoch, with a fractional part, as in the reference lox
 pub struct Clock;
 
 impl LoxCallable for Clock {
@@ -15,13 +30,1592 @@ impl LoxCallable for Clock {
     fn call(
         &self,
         _: &mut crate::interpreter::Interpreter,
+        _: &Token,
+        _: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        Ok(Rc::new(RefCell::new(LoxType::Number(since_epoch().as_secs_f64()))))
+    }
+}
+
+// whole milliseconds since the unix epoch, what clock gave before it returned seconds
+pub struct ClockMillis;
+
+impl LoxCallable for ClockMillis {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        _: &Token,
+        _: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        Ok(Rc::new(RefCell::new(LoxType::Number(since_epoch().as_millis() as f64))))
+    }
+}
+
+// renders a value as lox source which parse_value turns back into an equal value
+pub struct Stringify;
+
+impl Stringify {
+    // seen holds the lists and maps being written, to catch one that contains itself
+    fn literal(value: &LoxType, paren: &Token, seen: &mut Vec<*const ()>) -> Result<String, RuntimeException> {
+        match value {
+            LoxType::Number(n) if n.is_finite() => Ok(n.to_string()),
+            LoxType::Strang(s) if !s.contains('"') => Ok(format!("\"{}\"", s)),
+            LoxType::Bool(b) => Ok(b.to_string()),
+            LoxType::Nil => Ok("nil".to_string()),
+            LoxType::List(l) => {
+                Stringify::enter(Rc::as_ptr(l) as *const (), paren, seen)?;
+                let elements = l
+                    .borrow()
+                    .iter()
+                    .map(|element| Stringify::literal(&element.borrow(), paren, seen))
+                    .collect::<Result<Vec<_>, _>>()?;
+                seen.pop();
+                Ok(format!("[{}]", elements.join(", ")))
+            }
+            // keys are sorted so the same map always gives the same string
+            LoxType::Map(m) => {
+                Stringify::enter(Rc::as_ptr(m) as *const (), paren, seen)?;
+                let m = m.borrow();
+                let mut keys: Vec<&String> = m.keys().collect();
+                keys.sort();
+                let entries = keys
+                    .into_iter()
+                    .map(|key| {
+                        let key_literal = Stringify::literal(&LoxType::Strang(key.clone()), paren, seen)?;
+                        let value = Stringify::literal(&m[key].borrow(), paren, seen)?;
+                        Ok(format!("{}: {}", key_literal, value))
+                    })
+                    .collect::<Result<Vec<_>, RuntimeException>>()?;
+                seen.pop();
+                Ok(format!("{{{}}}", entries.join(", ")))
+            }
+            LoxType::Number(_)
+            | LoxType::Strang(_)
+            | LoxType::Function(_)
+            | LoxType::Class(_)
+            | LoxType::Instance(_)
+            | LoxType::Lazy(_) => Err(RuntimeException::new(
+                paren.clone(),
+                &format!("Unable to stringify {:?}", value),
+            )),
+        }
+    }
+
+    fn enter(collection: *const (), paren: &Token, seen: &mut Vec<*const ()>) -> Result<(), RuntimeException> {
+        if seen.contains(&collection) {
+            return Err(RuntimeException::new(
+                paren.clone(),
+                "Unable to stringify a list or map that contains itself",
+            ));
+        }
+        seen.push(collection);
+        Ok(())
+    }
+}
+
+impl LoxCallable for Stringify {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let literal = Stringify::literal(&arguments[0].borrow(), paren, &mut vec![])?;
+        Ok(Rc::new(RefCell::new(LoxType::Strang(literal))))
+    }
+}
+
+pub struct ParseValue;
+
+impl ParseValue {
+    // only literal expressions are values, anything that would need evaluating isn't accepted
+    fn value(expr: &Expr) -> Option<LoxType> {
+        match expr {
+            Expr::Literal { value, .. } => Some(value.clone()),
+            Expr::Grouping { expression } => ParseValue::value(expression),
+            Expr::Unary { operator, right } if operator.token_type == TokenType::Minus => {
+                match ParseValue::value(right)? {
+                    LoxType::Number(n) => Some(LoxType::Number(-n)),
+                    _ => None,
+                }
+            }
+            Expr::ArrayLiteral { elements, .. } => {
+                let elements = elements
+                    .iter()
+                    .map(|element| Some(Rc::new(RefCell::new(ParseValue::value(element)?))))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(LoxType::List(Rc::new(RefCell::new(elements))))
+            }
+            // keys follow map literals, strings or numbers in their string form
+            Expr::MapLiteral { entries, .. } => {
+                let mut map = HashMap::new();
+                for (key, value) in entries {
+                    let key = match ParseValue::value(key)? {
+                        LoxType::Strang(s) => s,
+                        LoxType::Number(n) => n.to_string(),
+                        _ => return None,
+                    };
+                    map.insert(key, Rc::new(RefCell::new(ParseValue::value(value)?)));
+                }
+                Some(LoxType::Map(Rc::new(RefCell::new(map))))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl LoxCallable for ParseValue {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let source = match &*arguments[0].borrow() {
+            LoxType::Strang(s) => s.clone(),
+            other => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("parse_value expects a string, found {:?}", other),
+                ))
+            }
+        };
+
+        let reporter = ErrorReporter::new();
+        let tokens = Lexer::new(&source, &reporter).collect_tokens();
+        let value = if !reporter.had_error() {
+            Parser::new(tokens, &reporter)
+                .parse_expression()
+                .as_ref()
+                .and_then(ParseValue::value)
+        } else {
+            None
+        };
+
+        match value {
+            Some(v) => Ok(Rc::new(RefCell::new(v))),
+            None => Err(RuntimeException::new(
+                paren.clone(),
+                &format!("{} is not a value literal", source),
+            )),
+        }
+    }
+}
+
+pub struct Flush;
+
+impl LoxCallable for Flush {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
         _: Vec<Rc<RefCell<LoxType>>>,
     ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
-        Ok(Rc::new(RefCell::new(LoxType::Number(
-            std::time::SystemTime::now()
-                .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as f32,
-        ))))
+        match interpreter.flush() {
+            Ok(_) => Ok(Rc::new(RefCell::new(LoxType::Nil))),
+            Err(e) => Err(RuntimeException::new(
+                paren.clone(),
+                &format!("Unable to flush output: {}", e),
+            )),
+        }
+    }
+}
+
+// reads an environment variable, nil if it isn't set
+pub struct Env;
+
+impl LoxCallable for Env {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        if !interpreter.allow_io() {
+            return Err(RuntimeException::new(
+                paren.clone(),
+                "env is unavailable, io is disabled in this interpreter",
+            ));
+        }
+
+        let name = match &*arguments[0].borrow() {
+            LoxType::Strang(s) => s.clone(),
+            other => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("env expects a string, found {:?}", other),
+                ))
+            }
+        };
+
+        match std::env::var(&name) {
+            Ok(value) => Ok(Rc::new(RefCell::new(LoxType::Strang(value)))),
+            Err(std::env::VarError::NotPresent) => Ok(Rc::new(RefCell::new(LoxType::Nil))),
+            Err(e) => Err(RuntimeException::new(
+                paren.clone(),
+                &format!("Unable to read environment variable {}: {}", name, e),
+            )),
+        }
+    }
+}
+
+// characters in a string, elements in a list or entries in a map
+pub struct Len;
+
+impl LoxCallable for Len {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let len = match &*arguments[0].borrow() {
+            LoxType::Strang(s) => s.chars().count(),
+            LoxType::List(l) => l.borrow().len(),
+            LoxType::Map(m) => m.borrow().len(),
+            other => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("len expects a string, list or map, found {:?}", other),
+                ))
+            }
+        };
+        Ok(Rc::new(RefCell::new(LoxType::Number(len as f64))))
+    }
+}
+
+// the printed form of any value
+pub struct Str;
+
+impl LoxCallable for Str {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        _: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let s = arguments[0].borrow().to_string();
+        Ok(Rc::new(RefCell::new(LoxType::Strang(s))))
+    }
+}
+
+// parses a string holding a number
+pub struct Num;
+
+impl LoxCallable for Num {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        match &*arguments[0].borrow() {
+            LoxType::Strang(s) => match s.parse::<f64>() {
+                Ok(n) => Ok(Rc::new(RefCell::new(LoxType::Number(n)))),
+                Err(_) => Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("{} is not a valid number", s),
+                )),
+            },
+            other => Err(RuntimeException::new(
+                paren.clone(),
+                &format!("num expects a string, found {:?}", other),
+            )),
+        }
+    }
+}
+
+fn string_argument(name: &str, value: &LoxType, paren: &Token) -> Result<String, RuntimeException> {
+    match value {
+        LoxType::Strang(s) => Ok(s.clone()),
+        other => Err(RuntimeException::new(
+            paren.clone(),
+            &format!("{} expects a string, found {:?}", name, other),
+        )),
+    }
+}
+
+type ListRef = Rc<RefCell<Vec<Rc<RefCell<LoxType>>>>>;
+
+// lists are shared, so this is the same list the caller holds
+fn list_argument(name: &str, value: &LoxType, paren: &Token) -> Result<ListRef, RuntimeException> {
+    match value {
+        LoxType::List(l) => Ok(Rc::clone(l)),
+        other => Err(RuntimeException::new(
+            paren.clone(),
+            &format!("{} expects a list, found {:?}", name, other),
+        )),
+    }
+}
+
+// calls a method from further up an instance's class chain than super reaches, e.g. a
+// grandparent's method its parent overrides. The search starts at the instance's superclass,
+// or at the ancestor given after the arguments
+pub struct Base;
+
+impl LoxCallable for Base {
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn max_arity(&self) -> usize {
+        4
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let class = match &*arguments[0].borrow() {
+            LoxType::Instance(i) => i.class().clone(),
+            other => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("base expects an instance, found {:?}", other),
+                ))
+            }
+        };
+        let name = string_argument("base", &arguments[1].borrow(), paren)?;
+        let method_arguments = list_argument("base", &arguments[2].borrow(), paren)?.borrow().clone();
+
+        let start = match arguments.get(3).map(|a| a.borrow().clone()) {
+            None => class.superclass().cloned(),
+            Some(LoxType::Class(ancestor)) => {
+                let mut current = class.superclass().cloned();
+                while let Some(c) = current.as_ref().filter(|c| ***c != ancestor) {
+                    current = c.superclass().cloned();
+                }
+                if current.is_none() {
+                    return Err(RuntimeException::new(
+                        paren.clone(),
+                        &format!("{} is not an ancestor of {}", ancestor, class),
+                    ));
+                }
+                current
+            }
+            Some(other) => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("base expects a class to start from, found {:?}", other),
+                ))
+            }
+        };
+
+        let method = start.and_then(|c| c.find_method(&name)).ok_or_else(|| {
+            RuntimeException::new(
+                paren.clone(),
+                &format!("No method {} on any ancestor of {}", name, class),
+            )
+        })?;
+        let bound = method.bind(Rc::clone(&arguments[0]));
+        interpreter.call_function(&bound, paren, method_arguments)
+    }
+}
+
+// the named method of a value, bound to it. Only instances have methods
+fn find_method(value: &Rc<RefCell<LoxType>>, name: &str) -> Option<LoxFunction> {
+    match &*value.borrow() {
+        LoxType::Instance(i) => i.class().find_method(name),
+        _ => None,
+    }
+    .map(|method| method.bind(Rc::clone(value)))
+}
+
+// whether a value has a method by the given name, for duck typing
+pub struct HasMethod;
+
+impl LoxCallable for HasMethod {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let name = string_argument("has_method", &arguments[1].borrow(), paren)?;
+        let found = find_method(&arguments[0], &name).is_some();
+        Ok(Rc::new(RefCell::new(LoxType::Bool(found))))
+    }
+}
+
+// calls a method with a list of arguments if the value has it, otherwise gives back nil
+pub struct CallIf;
+
+impl LoxCallable for CallIf {
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let name = string_argument("call_if", &arguments[1].borrow(), paren)?;
+        let method_arguments = list_argument("call_if", &arguments[2].borrow(), paren)?.borrow().clone();
+        match find_method(&arguments[0], &name) {
+            Some(method) => interpreter.call_function(&method, paren, method_arguments),
+            None => Ok(Rc::new(RefCell::new(LoxType::Nil))),
+        }
+    }
+}
+
+// adds a value to the end of a list, in place
+pub struct Push;
+
+impl LoxCallable for Push {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let list = list_argument("push", &arguments[0].borrow(), paren)?;
+        list.borrow_mut().push(Rc::clone(&arguments[1]));
+        Ok(Rc::new(RefCell::new(LoxType::Nil)))
+    }
+}
+
+// removes and gives back the last value of a list, nil if it's empty
+pub struct Pop;
+
+impl LoxCallable for Pop {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let list = list_argument("pop", &arguments[0].borrow(), paren)?;
+        let last = list.borrow_mut().pop();
+        Ok(last.unwrap_or_else(|| Rc::new(RefCell::new(LoxType::Nil))))
+    }
+}
+
+// whether any value in a list is == to the one given
+pub struct Contains;
+
+impl LoxCallable for Contains {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let list = list_argument("contains", &arguments[0].borrow(), paren)?;
+        let value = arguments[1].borrow();
+        let found = list.borrow().iter().any(|item| *item.borrow() == *value);
+        Ok(Rc::new(RefCell::new(LoxType::Bool(found))))
+    }
+}
+
+// a whole number written in base 2 to 36, 10 unless given. nil if it isn't one
+pub struct ParseInt;
+
+impl LoxCallable for ParseInt {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn max_arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let s = string_argument("parse_int", &arguments[0].borrow(), paren)?;
+        let radix = match arguments.get(1) {
+            Some(radix) => number_argument("parse_int", &radix.borrow(), paren)?,
+            None => 10.0,
+        };
+        if radix.fract() != 0.0 || !(2.0..=36.0).contains(&radix) {
+            return Err(RuntimeException::new(
+                paren.clone(),
+                &format!("parse_int radix must be a whole number from 2 to 36, found {}", radix),
+            ));
+        }
+
+        let value = match i64::from_str_radix(&s, radix as u32) {
+            Ok(n) => LoxType::Number(n as f64),
+            Err(_) => LoxType::Nil,
+        };
+        Ok(Rc::new(RefCell::new(value)))
+    }
+}
+
+// a number with or without a fractional part. nil if it isn't one
+pub struct ParseFloat;
+
+impl LoxCallable for ParseFloat {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let s = string_argument("parse_float", &arguments[0].borrow(), paren)?;
+        let value = match s.parse::<f64>() {
+            Ok(n) => LoxType::Number(n),
+            Err(_) => LoxType::Nil,
+        };
+        Ok(Rc::new(RefCell::new(value)))
+    }
+}
+
+// the whole numbers from start up to but not including end, counting by step (1 unless given).
+// a negative step counts down instead
+pub struct Range;
+
+impl LoxCallable for Range {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn max_arity(&self) -> usize {
+        3
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let mut bounds = vec![];
+        for argument in arguments.iter() {
+            let n = number_argument("range", &argument.borrow(), paren)?;
+            if n.fract() != 0.0 {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("range expects whole numbers, found {}", n),
+                ));
+            }
+            bounds.push(n as i64);
+        }
+        let (start, end) = (bounds[0], bounds[1]);
+        let step = bounds.get(2).copied().unwrap_or(1);
+        if step == 0 {
+            return Err(RuntimeException::new(paren.clone(), "range step can't be 0"));
+        }
+
+        let mut items = vec![];
+        let mut n = start;
+        while (step > 0 && n < end) || (step < 0 && n > end) {
+            items.push(Rc::new(RefCell::new(LoxType::Number(n as f64))));
+            n += step;
+        }
+        Ok(Rc::new(RefCell::new(LoxType::List(Rc::new(RefCell::new(items))))))
+    }
+}
+
+// raises if a condition is falsy, with an optional message saying what went wrong
+pub struct Assert;
+
+impl LoxCallable for Assert {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn max_arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let message = match arguments.get(1) {
+            Some(message) => format!(
+                "assertion failed: {}",
+                string_argument("assert_true", &message.borrow(), paren)?
+            ),
+            None => "assertion failed".to_string(),
+        };
+        if crate::interpreter::Interpreter::is_truthy(&arguments[0].borrow()) {
+            Ok(Rc::new(RefCell::new(LoxType::Nil)))
+        } else {
+            Err(RuntimeException::new(paren.clone(), &message))
+        }
+    }
+}
+
+// calls a function expecting it to raise an error, optionally one whose message
+// contains a given substring, and raises if it doesn't
+pub struct AssertThrows;
+
+impl LoxCallable for AssertThrows {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn max_arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let function = match &*arguments[0].borrow() {
+            // taking arguments it would always fail to be called, which would count as throwing
+            LoxType::Function(f) if f.arity() == 0 => Rc::clone(f),
+            other => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("assert_throws expects a function taking no arguments, found {:?}", other),
+                ))
+            }
+        };
+        let substring = match arguments.get(1).map(|a| a.borrow().clone()) {
+            None => None,
+            Some(LoxType::Strang(s)) => Some(s),
+            Some(other) => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("assert_throws expects a string to match, found {:?}", other),
+                ))
+            }
+        };
+
+        match interpreter.call_function(function.as_ref(), paren, vec![]) {
+            Ok(_) => Err(RuntimeException::new(
+                paren.clone(),
+                "expected an error but none occurred",
+            )),
+            Err(e) => match substring {
+                Some(s) if !e.message.contains(&s) => Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("expected an error containing {} but got: {}", s, e.message),
+                )),
+                _ => Ok(Rc::new(RefCell::new(LoxType::Nil))),
+            },
+        }
+    }
+}
+
+// the name of a value's type
+pub struct TypeOf;
+
+impl LoxCallable for TypeOf {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        _: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let name = match &*arguments[0].borrow() {
+            LoxType::Number(_) => "number",
+            LoxType::Strang(_) => "string",
+            LoxType::Bool(_) => "bool",
+            LoxType::Nil => "nil",
+            LoxType::Function(_) => "function",
+            LoxType::Class(_) => "class",
+            LoxType::Instance(_) => "instance",
+            LoxType::List(_) => "list",
+            LoxType::Map(_) => "map",
+            LoxType::Lazy(_) => "lazy",
+        };
+        Ok(Rc::new(RefCell::new(LoxType::Strang(name.to_string()))))
+    }
+}
+
+// wraps a function taking no arguments in a thunk, which force runs at most once
+pub struct Lazy;
+
+impl LoxCallable for Lazy {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let function = match &*arguments[0].borrow() {
+            LoxType::Function(f) if f.arity() == 0 => Rc::clone(f),
+            other => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("lazy expects a function taking no arguments, found {:?}", other),
+                ))
+            }
+        };
+        let state = LazyState::Unevaluated(function);
+        Ok(Rc::new(RefCell::new(LoxType::Lazy(Rc::new(RefCell::new(state))))))
+    }
+}
+
+// the first force of a thunk runs its function and keeps the result, later ones hand it back
+pub struct Force;
+
+impl LoxCallable for Force {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let thunk = match &*arguments[0].borrow() {
+            LoxType::Lazy(l) => Rc::clone(l),
+            other => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("force expects a lazy value, found {:?}", other),
+                ))
+            }
+        };
+
+        if let LazyState::Evaluated(value) = &*thunk.borrow() {
+            return Ok(Rc::new(RefCell::new(value.clone())));
+        }
+        let function = match thunk.replace(LazyState::Evaluating) {
+            LazyState::Unevaluated(function) => function,
+            _ => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    "lazy value forced while it's being evaluated",
+                ))
+            }
+        };
+
+        match interpreter.call_function(function.as_ref(), paren, vec![]) {
+            Ok(result) => {
+                *thunk.borrow_mut() = LazyState::Evaluated(result.borrow().clone());
+                Ok(result)
+            }
+            // left to be tried again by the next force
+            Err(e) => {
+                *thunk.borrow_mut() = LazyState::Unevaluated(function);
+                Err(e)
+            }
+        }
+    }
+}
+
+fn number_argument(name: &str, value: &LoxType, paren: &Token) -> Result<f64, RuntimeException> {
+    match value {
+        LoxType::Number(n) => Ok(*n),
+        other => Err(RuntimeException::new(
+            paren.clone(),
+            &format!("{} expects a number, found {:?}", name, other),
+        )),
+    }
+}
+
+// a maths function of one number, e.g. sqrt
+pub struct UnaryMath {
+    name: &'static str,
+    op: fn(f64) -> f64,
+}
+
+impl UnaryMath {
+    pub fn new(name: &'static str, op: fn(f64) -> f64) -> Self {
+        Self { name, op }
+    }
+}
+
+impl LoxCallable for UnaryMath {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let n = number_argument(self.name, &arguments[0].borrow(), paren)?;
+        Ok(Rc::new(RefCell::new(LoxType::Number((self.op)(n)))))
+    }
+}
+
+// a maths function of two numbers, e.g. pow
+pub struct BinaryMath {
+    name: &'static str,
+    op: fn(f64, f64) -> f64,
+}
+
+impl BinaryMath {
+    pub fn new(name: &'static str, op: fn(f64, f64) -> f64) -> Self {
+        Self { name, op }
+    }
+}
+
+impl LoxCallable for BinaryMath {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let a = number_argument(self.name, &arguments[0].borrow(), paren)?;
+        let b = number_argument(self.name, &arguments[1].borrow(), paren)?;
+        Ok(Rc::new(RefCell::new(LoxType::Number((self.op)(a, b)))))
+    }
+}
+
+// reads a line from the interpreter's input, after writing an optional prompt.
+// nil once the input runs out
+pub struct ReadLine;
+
+impl LoxCallable for ReadLine {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn max_arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let io_error =
+            |e: std::io::Error| RuntimeException::new(paren.clone(), &format!("Unable to read input: {}", e));
+
+        // the prompt, or anything printed before, must show before waiting on input
+        let prompt = arguments.first().map(|p| p.borrow().to_string()).unwrap_or_default();
+        interpreter.write_prompt(&prompt).map_err(io_error)?;
+
+        match interpreter.read_line().map_err(io_error)? {
+            Some(line) => Ok(Rc::new(RefCell::new(LoxType::Strang(line)))),
+            None => Ok(Rc::new(RefCell::new(LoxType::Nil))),
+        }
+    }
+}
+
+pub struct ReadAll;
+
+impl LoxCallable for ReadAll {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        _arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        if !interpreter.allow_io() {
+            return Err(RuntimeException::new(
+                paren.clone(),
+                "read_all is unavailable, io is disabled in this interpreter",
+            ));
+        }
+
+        match interpreter.read_all_input() {
+            Ok(contents) => Ok(Rc::new(RefCell::new(LoxType::Strang(contents)))),
+            Err(e) => Err(RuntimeException::new(
+                paren.clone(),
+                &format!("Unable to read input: {}", e),
+            )),
+        }
+    }
+}
+
+// a function turning one string into another, e.g. upper
+pub struct StringMap {
+    name: &'static str,
+    op: fn(&str) -> String,
+}
+
+impl StringMap {
+    pub fn new(name: &'static str, op: fn(&str) -> String) -> Self {
+        Self { name, op }
+    }
+}
+
+impl LoxCallable for StringMap {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let s = string_argument(self.name, &arguments[0].borrow(), paren)?;
+        Ok(Rc::new(RefCell::new(LoxType::Strang((self.op)(&s)))))
+    }
+}
+
+// the characters of a string from start up to but not including end
+pub struct Substring;
+
+impl LoxCallable for Substring {
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let s = string_argument("substring", &arguments[0].borrow(), paren)?;
+        let start = number_argument("substring", &arguments[1].borrow(), paren)?;
+        let end = number_argument("substring", &arguments[2].borrow(), paren)?;
+
+        let len = s.chars().count() as f64;
+        if start.fract() != 0.0 || end.fract() != 0.0 || start < 0.0 || start > end || end > len {
+            return Err(RuntimeException::new(
+                paren.clone(),
+                &format!(
+                    "substring range {} to {} is out of bounds for length {}",
+                    start, end, len
+                ),
+            ));
+        }
+
+        let sub = s.chars().skip(start as usize).take((end - start) as usize).collect();
+        Ok(Rc::new(RefCell::new(LoxType::Strang(sub))))
+    }
+}
+
+// the pieces of a string between each separator, or its characters if the separator is empty
+pub struct Split;
+
+impl LoxCallable for Split {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let s = string_argument("split", &arguments[0].borrow(), paren)?;
+        let separator = string_argument("split", &arguments[1].borrow(), paren)?;
+
+        let pieces: Vec<String> = if separator.is_empty() {
+            s.chars().map(String::from).collect()
+        } else {
+            s.split(separator.as_str()).map(String::from).collect()
+        };
+        let items = pieces
+            .into_iter()
+            .map(|piece| Rc::new(RefCell::new(LoxType::Strang(piece))))
+            .collect();
+        Ok(Rc::new(RefCell::new(LoxType::List(Rc::new(RefCell::new(items))))))
+    }
+}
+
+// the unicode code points of a string's characters, as a list of numbers
+pub struct Codes;
+
+impl LoxCallable for Codes {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let s = string_argument("codes", &arguments[0].borrow(), paren)?;
+        let codes = s
+            .chars()
+            .map(|c| Rc::new(RefCell::new(LoxType::Number(c as u32 as f64))))
+            .collect();
+        Ok(Rc::new(RefCell::new(LoxType::List(Rc::new(RefCell::new(codes))))))
+    }
+}
+
+// the string made of a list of code points, the inverse of codes. Each must be a whole
+// number that's a valid unicode scalar value
+pub struct StringFromCodes;
+
+impl LoxCallable for StringFromCodes {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let codes = list_argument("string_from_codes", &arguments[0].borrow(), paren)?;
+        let mut s = String::new();
+        for code in codes.borrow().iter() {
+            let code = number_argument("string_from_codes", &code.borrow(), paren)?;
+            let c = (code.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(&code))
+                .then(|| char::from_u32(code as u32))
+                .flatten();
+            match c {
+                Some(c) => s.push(c),
+                None => {
+                    return Err(RuntimeException::new(
+                        paren.clone(),
+                        &format!("string_from_codes found {}, which isn't a code point", code),
+                    ))
+                }
+            }
+        }
+        Ok(Rc::new(RefCell::new(LoxType::Strang(s))))
+    }
+}
+
+// a format template split up ahead of time, so a call only has to fill in the holes
+#[derive(Debug)]
+pub struct ParsedTemplate {
+    pieces: Vec<TemplatePiece>,
+    holes: usize,
+}
+
+#[derive(Debug)]
+enum TemplatePiece {
+    Text(String),
+    Hole,
+}
+
+impl ParsedTemplate {
+    // {} is a hole, {{ and }} stand for a literal brace
+    fn parse(template: &str) -> Result<Self, String> {
+        let mut pieces = vec![];
+        let mut text = String::new();
+        let mut holes = 0;
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    text.push(c);
+                }
+                ('{', Some('}')) => {
+                    chars.next();
+                    if !text.is_empty() {
+                        pieces.push(TemplatePiece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(TemplatePiece::Hole);
+                    holes += 1;
+                }
+                ('{', _) | ('}', _) => {
+                    return Err(format!("format template has an unmatched {}, use {}{} for a literal one", c, c, c))
+                }
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(TemplatePiece::Text(text));
+        }
+        Ok(Self { pieces, holes })
+    }
+
+    fn fill(&self, values: &[Rc<RefCell<LoxType>>]) -> String {
+        let mut values = values.iter();
+        let mut filled = String::new();
+        for piece in &self.pieces {
+            match piece {
+                TemplatePiece::Text(text) => filled.push_str(text),
+                TemplatePiece::Hole => filled.push_str(&values.next().unwrap().borrow().to_string()),
+            }
+        }
+        filled
+    }
+}
+
+// how many parsed templates format keeps. A script building templates on the fly would
+// otherwise grow the cache without end, so once it's full it's emptied and starts over
+const MAX_CACHED_TEMPLATES: usize = 64;
+
+// fills each {} in a template with the next value, as print would show it. Templates are
+// usually formatted over and over in a loop, so each is only parsed the first time
+pub struct Format {
+    templates: RefCell<HashMap<String, Rc<ParsedTemplate>>>,
+    parses: Cell<usize>,
+    // the interpreter's --max-args limit
+    max_args: Rc<Cell<usize>>,
+}
+
+impl Format {
+    pub fn new(max_args: Rc<Cell<usize>>) -> Self {
+        Self {
+            templates: RefCell::new(HashMap::new()),
+            parses: Cell::new(0),
+            max_args,
+        }
+    }
+
+    fn template(&self, template: &str, paren: &Token) -> Result<Rc<ParsedTemplate>, RuntimeException> {
+        if let Some(parsed) = self.templates.borrow().get(template) {
+            return Ok(Rc::clone(parsed));
+        }
+        self.parses.set(self.parses.get() + 1);
+        let parsed = ParsedTemplate::parse(template)
+            .map_err(|message| RuntimeException::new(paren.clone(), &message))?;
+        let parsed = Rc::new(parsed);
+        let mut templates = self.templates.borrow_mut();
+        if templates.len() >= MAX_CACHED_TEMPLATES {
+            templates.clear();
+        }
+        templates.insert(template.to_string(), Rc::clone(&parsed));
+        Ok(parsed)
+    }
+}
+
+impl LoxCallable for Format {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn max_arity(&self) -> usize {
+        self.max_args.get()
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let template = string_argument("format", &arguments[0].borrow(), paren)?;
+        let parsed = self.template(&template, paren)?;
+
+        let values = &arguments[1..];
+        if values.len() != parsed.holes {
+            return Err(RuntimeException::new(
+                paren.clone(),
+                &format!(
+                    "format template has {} placeholders but {} values were given",
+                    parsed.holes,
+                    values.len()
+                ),
+            ));
+        }
+        Ok(Rc::new(RefCell::new(LoxType::Strang(parsed.fill(values)))))
+    }
+}
+
+// the first of its arguments that isn't nil, or nil if they all are. Being a native, every
+// argument is evaluated before it's called, even the ones after the one returned
+pub struct Coalesce {
+    // the interpreter's --max-args limit
+    max_args: Rc<Cell<usize>>,
+}
+
+impl Coalesce {
+    pub fn new(max_args: Rc<Cell<usize>>) -> Self {
+        Self { max_args }
+    }
+}
+
+impl LoxCallable for Coalesce {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn max_arity(&self) -> usize {
+        self.max_args.get()
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        _: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        Ok(arguments
+            .into_iter()
+            .find(|argument| !matches!(&*argument.borrow(), LoxType::Nil))
+            .unwrap_or_else(|| Rc::new(RefCell::new(LoxType::Nil))))
+    }
+}
+
+// calls a function taking no arguments up to times times, handing back its result the first
+// time it doesn't raise an error. If every attempt does, the last error is raised
+pub struct Retry;
+
+impl LoxCallable for Retry {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let function = match &*arguments[0].borrow() {
+            LoxType::Function(f) if f.arity() == 0 => Rc::clone(f),
+            other => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("retry expects a function taking no arguments, found {:?}", other),
+                ))
+            }
+        };
+        let times = number_argument("retry", &arguments[1].borrow(), paren)?;
+        if times < 1.0 || times.fract() != 0.0 {
+            return Err(RuntimeException::new(
+                paren.clone(),
+                &format!("retry expects a whole number of times, at least 1, found {}", times),
+            ));
+        }
+
+        let mut attempts_left = times as u64;
+        loop {
+            attempts_left -= 1;
+            match interpreter.call_function(function.as_ref(), paren, vec![]) {
+                Err(_) if attempts_left > 0 => {}
+                result => return result,
+            }
+        }
+    }
+}
+
+// calls a function with a list of arguments, giving back {"ok": true, "value": result}, or
+// {"ok": false, "error": message} if it raised a runtime error, rather than raising it
+pub struct TryCall;
+
+impl LoxCallable for TryCall {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let function = match &*arguments[0].borrow() {
+            LoxType::Function(f) => Rc::clone(f),
+            other => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("try_call expects a function, found {:?}", other),
+                ))
+            }
+        };
+        let function_arguments = list_argument("try_call", &arguments[1].borrow(), paren)?.borrow().clone();
+
+        let mut result = HashMap::new();
+        match interpreter.call_function(function.as_ref(), paren, function_arguments) {
+            Ok(value) => {
+                result.insert("ok".to_string(), Rc::new(RefCell::new(LoxType::Bool(true))));
+                result.insert("value".to_string(), value);
+            }
+            Err(e) => {
+                result.insert("ok".to_string(), Rc::new(RefCell::new(LoxType::Bool(false))));
+                result.insert("error".to_string(), Rc::new(RefCell::new(LoxType::Strang(e.message))));
+            }
+        }
+        Ok(Rc::new(RefCell::new(LoxType::Map(Rc::new(RefCell::new(result))))))
+    }
+}
+
+// the element of a list with the smallest (min_by) or largest (max_by) number from a key
+// function, the first one on a tie
+pub struct ExtremeBy {
+    name: &'static str,
+    largest: bool,
+}
+
+impl ExtremeBy {
+    pub fn new(name: &'static str, largest: bool) -> Self {
+        Self { name, largest }
+    }
+}
+
+impl LoxCallable for ExtremeBy {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let items = list_argument(self.name, &arguments[0].borrow(), paren)?.borrow().clone();
+        let key = match &*arguments[1].borrow() {
+            LoxType::Function(f) if f.arity() <= 1 && f.max_arity() >= 1 => Rc::clone(f),
+            other => {
+                return Err(RuntimeException::new(
+                    paren.clone(),
+                    &format!("{} expects a key function taking one argument, found {:?}", self.name, other),
+                ))
+            }
+        };
+        if items.is_empty() {
+            return Err(RuntimeException::new(
+                paren.clone(),
+                &format!("{} of an empty list", self.name),
+            ));
+        }
+
+        let mut best: Option<(f64, Rc<RefCell<LoxType>>)> = None;
+        for item in items {
+            let value = interpreter.call_function(key.as_ref(), paren, vec![Rc::clone(&item)])?;
+            let k = match &*value.borrow() {
+                LoxType::Number(n) => *n,
+                other => {
+                    return Err(RuntimeException::new(
+                        paren.clone(),
+                        &format!("{} expects the key function to return a number, found {:?}", self.name, other),
+                    ))
+                }
+            };
+            let better = match &best {
+                None => true,
+                Some((best_key, _)) if self.largest => k > *best_key,
+                Some((best_key, _)) => k < *best_key,
+            };
+            if better {
+                best = Some((k, item));
+            }
+        }
+        Ok(best.map(|(_, item)| item).unwrap())
+    }
+}
+
+// what the exit native does with its code. The process ends unless an embedder swaps in a
+// handler of its own, e.g. to stop the script with an error rather than kill the host
+pub trait ExitHandler {
+    // only returns when the host wants the script stopped with an error, the message given
+    fn exit(&mut self, code: i32) -> String;
+}
+
+pub struct ProcessExit;
+
+impl ExitHandler for ProcessExit {
+    fn exit(&mut self, code: i32) -> String {
+        std::process::exit(code)
+    }
+}
+
+// ends the program with a status code, which must be a whole number that fits an i32
+pub struct Exit;
+
+impl LoxCallable for Exit {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let code = number_argument("exit", &arguments[0].borrow(), paren)?;
+        if code.fract() != 0.0 || code < i32::MIN as f64 || code > i32::MAX as f64 {
+            return Err(RuntimeException::new(
+                paren.clone(),
+                &format!("exit expects a whole number status code, found {}", code),
+            ));
+        }
+        let message = interpreter.exit(code as i32);
+        Err(RuntimeException::new(paren.clone(), &message))
+    }
+}
+
+// how the sleep native waits. Embedders can swap in their own, e.g. so tests don't block
+pub trait Sleeper {
+    fn sleep(&mut self, duration: std::time::Duration);
+}
+
+pub struct ThreadSleep;
+
+impl Sleeper for ThreadSleep {
+    fn sleep(&mut self, duration: std::time::Duration) {
+        std::thread::sleep(duration)
+    }
+}
+
+// pauses the program for a number of milliseconds
+pub struct Sleep;
+
+impl LoxCallable for Sleep {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let millis = number_argument("sleep", &arguments[0].borrow(), paren)?;
+        if !millis.is_finite() || millis < 0.0 {
+            return Err(RuntimeException::new(
+                paren.clone(),
+                &format!("sleep expects a non-negative number of milliseconds, found {}", millis),
+            ));
+        }
+        interpreter.sleep(std::time::Duration::from_secs_f64(millis / 1000.0));
+        Ok(Rc::new(RefCell::new(LoxType::Nil)))
+    }
+}
+
+// a plain rust function as a native, for embedders registering their own with
+// Interpreter::register_native. It gets copies of the arguments, and an Err becomes a
+// runtime error at the call
+pub struct HostFunction {
+    name: String,
+    arity: usize,
+    function: Box<dyn Fn(Vec<LoxType>) -> Result<LoxType, String>>,
+}
+
+impl HostFunction {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        function: impl Fn(Vec<LoxType>) -> Result<LoxType, String> + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl LoxCallable for HostFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn call(
+        &self,
+        _: &mut crate::interpreter::Interpreter,
+        paren: &Token,
+        arguments: Vec<Rc<RefCell<LoxType>>>,
+    ) -> Result<Rc<RefCell<LoxType>>, RuntimeException> {
+        let arguments = arguments.iter().map(|a| a.borrow().clone()).collect();
+        match (self.function)(arguments) {
+            Ok(value) => Ok(Rc::new(RefCell::new(value))),
+            Err(message) => Err(RuntimeException::new(paren.clone(), &message)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common::LOX_MAX_ARGUMENT_COUNT, token};
+
+    fn paren() -> Token {
+        token!(RightParen, ")", (1, 1))
+    }
+
+    #[test]
+    fn format_parses_each_template_once() {
+        let format = Format::new(Rc::new(Cell::new(LOX_MAX_ARGUMENT_COUNT)));
+        for _ in 0..3 {
+            format.template("{} and {}", &paren()).unwrap();
+        }
+        assert_eq!(format.parses.get(), 1);
+
+        format.template("just {}", &paren()).unwrap();
+        format.template("{} and {}", &paren()).unwrap();
+        assert_eq!(format.parses.get(), 2);
+    }
+
+    #[test]
+    fn format_cache_is_bounded() {
+        let format = Format::new(Rc::new(Cell::new(LOX_MAX_ARGUMENT_COUNT)));
+        for i in 0..MAX_CACHED_TEMPLATES * 3 {
+            format.template(&format!("{} #{}", "{}", i), &paren()).unwrap();
+            assert!(format.templates.borrow().len() <= MAX_CACHED_TEMPLATES);
+        }
+        assert_eq!(format.parses.get(), MAX_CACHED_TEMPLATES * 3);
     }
 }
@@ -1,33 +1,79 @@
-use std::vec::IntoIter;
+use std::cell::Cell;
 
 use crate::{
     common::{LoxType, Token, TokenType, LOX_MAX_ARGUMENT_COUNT},
     expr::Expr,
-    lox,
+    lox::{Diagnostic, ErrorReporter, Phase},
     stmt::Stmt,
     token,
 };
 
-pub struct Parser {
-    tokens: std::iter::Peekable<IntoIter<Token>>,
+pub struct Parser<'a> {
+    tokens: Vec<Token>,
+    // index of the next token to consume
+    current: usize,
+    reporter: &'a ErrorReporter,
+    // most arguments a call, or parameters a function, may have. None for no limit
+    max_args: Option<usize>,
+    // type of the last token consumed, so synchronize knows if a statement just ended
+    previous: Option<TokenType>,
+    // whether any error was reported at the end of the input, and whether any was before it
+    error_at_end: Cell<bool>,
+    error_before_end: Cell<bool>,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+impl<'a> Parser<'a> {
+    pub fn new(tokens: Vec<Token>, reporter: &'a ErrorReporter) -> Self {
         Self {
-            tokens: tokens.into_iter().peekable(),
+            tokens,
+            current: 0,
+            reporter,
+            max_args: Some(LOX_MAX_ARGUMENT_COUNT),
+            previous: None,
+            error_at_end: Cell::new(false),
+            error_before_end: Cell::new(false),
         }
     }
 
+    pub fn with_max_args(mut self, max_args: Option<usize>) -> Self {
+        self.max_args = max_args;
+        self
+    }
+
+    // after parsing, whether the only thing wrong was the source stopping partway through,
+    // e.g. an unclosed brace, so the repl knows to read another line rather than complain
+    pub fn needs_more_input(&self) -> bool {
+        self.error_at_end.get() && !self.error_before_end.get()
+    }
+
+    // only true for the first argument over the limit, so each list reports it once
+    fn just_exceeded_max_args(&self, count: usize) -> bool {
+        self.max_args.is_some_and(|max| count == max + 1)
+    }
+
     fn consume_token(&mut self) -> Option<Token> {
-        self.tokens.next()
+        let token = self.tokens.get(self.current).cloned();
+        if token.is_some() {
+            self.current += 1;
+        }
+        self.previous = token.as_ref().map(|t| t.token_type);
+        token
+    }
+
+    // the token `distance` past the next one, without consuming anything
+    fn peek_ahead(&self, distance: usize) -> Option<&Token> {
+        self.tokens.get(self.current + distance)
     }
 
     fn declaration(&mut self) -> Result<Stmt, ParseError> {
         if self.match_next_token(&[TokenType::Var]) {
             self.var_declaration()
+        } else if self.match_next_token(&[TokenType::Val]) {
+            self.val_declaration()
         } else if self.match_next_token(&[TokenType::Funct]) {
             self.function_declaration()
+        } else if self.match_next_token(&[TokenType::Pure]) {
+            self.pure_function_declaration()
         } else if self.match_next_token(&[TokenType::Class]) {
             self.class_declaration()
         } else {
@@ -50,35 +96,98 @@ impl Parser {
             TokenType::SemiColon,
             "Expect ';' after variable declaration",
         )?;
-        Ok(Stmt::Var { name, initializer })
+        Ok(Stmt::Var {
+            name,
+            initializer,
+            is_const: false,
+        })
+    }
+
+    fn val_declaration(&mut self) -> Result<Stmt, ParseError> {
+        // consume val token
+        self.consume_token();
+        let name = self.require_consume(TokenType::Identifier, "Expected constant name")?;
+        // it can never be given a value later, so needs one now
+        self.require_consume(TokenType::Equal, "Expect '=' after constant name")?;
+        let initializer = self.expression()?;
+
+        self.require_consume(
+            TokenType::SemiColon,
+            "Expect ';' after constant declaration",
+        )?;
+        Ok(Stmt::Var {
+            name,
+            initializer: Some(initializer),
+            is_const: true,
+        })
     }
 
     fn function_declaration(&mut self) -> Result<Stmt, ParseError> {
         // consume funct token
         self.consume_token();
-        self.function()
+        self.function(false)
     }
 
-    fn function(&mut self) -> Result<Stmt, ParseError> {
+    fn pure_function_declaration(&mut self) -> Result<Stmt, ParseError> {
+        // consume pure token
+        self.consume_token();
+        self.require_consume(TokenType::Funct, "Expect 'funct' after 'pure'")?;
+        self.function(true)
+    }
+
+    fn function(&mut self, pure: bool) -> Result<Stmt, ParseError> {
         let name = self.require_consume(
             TokenType::Identifier,
             "Expect 'funct' keyword be followed by function name",
         )?;
 
         self.require_consume(TokenType::LeftParen, "Expect '(' after function name")?;
+        let (parameters, body) = self.parameters_and_body()?;
 
+        Ok(Stmt::Function {
+            name,
+            parameters,
+            body,
+            pure,
+        })
+    }
+
+    // everything after the opening '(' of a function, named or anonymous
+    fn parameters_and_body(&mut self) -> Result<(Vec<Token>, Vec<Stmt>), ParseError> {
         let mut parameters = vec![];
+        let mut too_many = None;
+        let mut malformed = None;
         while !self.match_next_token(&[TokenType::RightParen, TokenType::EOF]) {
-            // still have args
-            parameters.push(self.consume_token().unwrap());
-            if parameters.len() > LOX_MAX_ARGUMENT_COUNT {
-                let next_tok = self.consume_token().unwrap();
-                self.error(&next_tok, "Exceeded max parameter count");
+            // a comma straight after '(' or another comma is caught here too
+            let parameter = match self.require_parameter_token(TokenType::Identifier, "Expect parameter name") {
+                Ok(parameter) => parameter,
+                Err(err) => {
+                    malformed = Some(err);
+                    break;
+                }
+            };
+            if self.just_exceeded_max_args(parameters.len() + 1) {
+                too_many = Some(self.error(&parameter, "Exceeded max parameter count"));
             }
+            parameters.push(parameter);
             if self.match_next_token(&[TokenType::RightParen]) {
                 break;
             }
-            self.require_consume(TokenType::Comma, "Expect parameters are comma seperated")?;
+            if let Err(err) = self.require_parameter_token(TokenType::Comma, "Expect parameters are comma seperated") {
+                malformed = Some(err);
+                break;
+            }
+        }
+
+        // only fail once the whole function is parsed, so synchronizing resumes after it
+        // rather than partway through the body
+        if let Some(err) = malformed {
+            self.skip_rest_of_parameters();
+            if self.match_next_token(&[TokenType::LeftBrace]) {
+                // the body's own mistakes are still reported
+                let _ = self.block();
+            }
+            return Err(err);
         }
 
         self.require_consume(
@@ -86,11 +195,36 @@ impl Parser {
             "Expect function parameter list to be closed with ')'",
         )?;
 
-        Ok(Stmt::Function {
-            name,
-            parameters,
-            body: self.block()?,
-        })
+        let body = self.block()?;
+        if let Some(err) = too_many {
+            return Err(err);
+        }
+
+        Ok((parameters, body))
+    }
+
+    // like require_consume, but a '{' or the end of the source is left alone, '{' most
+    // likely being the start of the body after a parameter list missing its ')'
+    fn require_parameter_token(&mut self, required: TokenType, error_message: &str) -> Result<Token, ParseError> {
+        match self.peek_ahead(0) {
+            Some(t) if matches!(t.token_type, TokenType::LeftBrace | TokenType::EOF) => {
+                Err(self.error(&t.clone(), error_message))
+            }
+            _ => self.require_consume(required, error_message),
+        }
+    }
+
+    // after a bad parameter, skips past the ')' closing the list, stopping short of the body
+    fn skip_rest_of_parameters(&mut self) {
+        let mut depth = usize::from(self.previous == Some(TokenType::LeftParen));
+        while !self.is_done() && !self.match_next_token(&[TokenType::LeftBrace]) {
+            match self.consume_token().map(|t| t.token_type) {
+                Some(TokenType::RightParen) if depth == 0 => return,
+                Some(TokenType::RightParen) => depth -= 1,
+                Some(TokenType::LeftParen) => depth += 1,
+                _ => {}
+            }
+        }
     }
 
     fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
@@ -101,6 +235,15 @@ impl Parser {
             TokenType::Identifier,
             "Expect class name after 'class' keyword",
         )?;
+
+        let superclass = if self.match_next_token(&[TokenType::Less]) {
+            self.consume_token();
+            let name = self.require_consume(TokenType::Identifier, "Expect superclass name after '<'")?;
+            Some(Expr::Variable { name })
+        } else {
+            None
+        };
+
         self.require_consume(TokenType::LeftBrace, "Expect '{' to open class body")?;
 
         let mut methods = vec![];
@@ -108,14 +251,15 @@ impl Parser {
             // more methods to come
             // consume meth token
             self.consume_token();
-            methods.push(self.function()?);
+            methods.push(self.function(false)?);
         }
 
         self.require_consume(TokenType::RightBrace, "Expect '}' to close class body")?;
 
         Ok(Stmt::Class {
             name,
-            methods: Box::new(methods),
+            superclass,
+            methods,
         })
     }
 
@@ -128,12 +272,20 @@ impl Parser {
             self.for_statement()
         } else if self.match_next_token(&[TokenType::Print]) {
             self.print_statement()
+        } else if self.match_next_token(&[TokenType::Assert]) {
+            self.assert_statement()
+        } else if self.match_next_token(&[TokenType::Defer]) {
+            self.defer_statement()
         } else if self.match_next_token(&[TokenType::Break]) {
             self.break_statement()
+        } else if self.match_next_token(&[TokenType::Continue]) {
+            self.continue_statement()
         } else if self.match_next_token(&[TokenType::Return]) {
             self.return_statement()
         } else if self.match_next_token(&[TokenType::LeftBrace]) {
+            let brace = self.peek_ahead(0).unwrap().clone();
             Ok(Stmt::Block {
+                brace,
                 statements: self.block()?,
             })
         } else {
@@ -142,8 +294,7 @@ impl Parser {
     }
 
     fn if_statement(&mut self) -> Result<Stmt, ParseError> {
-        // consume the if token
-        self.consume_token();
+        let keyword = self.consume_token().unwrap();
         self.require_consume(TokenType::LeftParen, "Expect '(' to open 'if' condition")?;
         let condition = self.expression()?;
         self.require_consume(TokenType::RightParen, "Expect ')' to close 'if' condition")?;
@@ -155,6 +306,7 @@ impl Parser {
             else_branch = Some(Box::new(self.statement()?));
         }
         Ok(Stmt::If {
+            keyword,
             condition,
             then_branch,
             else_branch,
@@ -162,8 +314,7 @@ impl Parser {
     }
 
     fn while_statement(&mut self) -> Result<Stmt, ParseError> {
-        // consume the while token
-        self.consume_token();
+        let keyword = self.consume_token().unwrap();
         self.require_consume(TokenType::LeftParen, "Expect '(' to open 'while' condition")?;
         let condition = self.expression()?;
         self.require_consume(
@@ -178,19 +329,39 @@ impl Parser {
             finally_branch = Some(Box::new(self.statement()?));
         }
         Ok(Stmt::While {
+            keyword,
             condition,
             then_branch,
+            increment: None,
             finally_branch,
+            fresh_bindings: vec![],
         })
     }
 
     fn for_statement(&mut self) -> Result<Stmt, ParseError> {
-        // consume the for token
-        self.consume_token();
+        let keyword = self.consume_token().unwrap();
         self.require_consume(TokenType::LeftParen, "Expect '(' to open 'for' clause")?;
 
+        let is_for_each = self.match_next_token(&[TokenType::Identifier])
+            && self.peek_ahead(1).is_some_and(|t| t.token_type == TokenType::In);
+        if is_for_each {
+            let variable = self.consume_token().unwrap();
+            // consume the in token
+            self.consume_token();
+            let iterable = self.expression()?;
+            self.require_consume(TokenType::RightParen, "Expect ')' to close 'for' clause")?;
+            return Ok(Stmt::ForEach {
+                keyword,
+                variable,
+                iterable,
+                body: Box::new(self.statement()?),
+            });
+        }
+
         let initializer;
         if self.match_next_token(&[TokenType::SemiColon]) {
+            // consume the ; token
+            self.consume_token();
             initializer = None;
         } else if self.match_next_token(&[TokenType::Var]) {
             initializer = Some(self.var_declaration()?);
@@ -214,42 +385,69 @@ impl Parser {
 
         self.require_consume(TokenType::RightParen, "Expect ')' to close 'for' clause")?;
 
-        let mut body = self.statement()?;
-
-        if increment.is_some() {
-            body = Stmt::Block {
-                statements: Box::new(vec![
-                    body,
-                    Stmt::Expression {
-                        expression: increment.unwrap(),
-                    },
-                ]),
-            };
-        }
-
-        if condition.is_some() {
-            body = Stmt::While {
-                condition: condition.unwrap(),
-                then_branch: Box::new(body),
-                finally_branch: None,
-            };
-        }
+        let fresh_bindings = match &initializer {
+            Some(Stmt::Var { name, .. }) => vec![name.clone()],
+            _ => vec![],
+        };
+        // no condition loops forever
+        let condition = condition.unwrap_or(Expr::Literal {
+            value: LoxType::Bool(true),
+            token: keyword.clone(),
+        });
+        let mut body = Stmt::While {
+            keyword,
+            condition,
+            then_branch: Box::new(self.statement()?),
+            increment,
+            finally_branch: None,
+            fresh_bindings,
+        };
 
-        if initializer.is_some() {
+        if let Some(initializer) = initializer {
             body = Stmt::Block {
-                statements: Box::new(vec![initializer.unwrap(), body]),
+                brace: body.token().clone(),
+                statements: vec![initializer, body],
             };
         }
 
         Ok(body)
     }
 
+    fn assert_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.consume_token().unwrap();
+        let condition = self.expression()?;
+        let message = if self.match_next_token(&[TokenType::Colon]) {
+            self.consume_token();
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.require_consume(TokenType::SemiColon, "Expect ';' after assertion")?;
+        Ok(Stmt::Assert {
+            keyword,
+            condition,
+            message,
+        })
+    }
+
     fn print_statement(&mut self) -> Result<Stmt, ParseError> {
-        // consume print token
-        self.consume_token();
+        let keyword = self.consume_token().unwrap();
         let value = self.expression()?;
         self.require_consume(TokenType::SemiColon, "Expect ';' after value")?;
-        Ok(Stmt::Print { expression: value })
+        Ok(Stmt::Print {
+            keyword,
+            expression: value,
+        })
+    }
+
+    fn defer_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.consume_token().unwrap();
+        let expression = self.expression()?;
+        self.require_consume(TokenType::SemiColon, "Expect ';' after deferred expression")?;
+        Ok(Stmt::Defer {
+            keyword,
+            expression,
+        })
     }
 
     fn break_statement(&mut self) -> Result<Stmt, ParseError> {
@@ -258,6 +456,12 @@ impl Parser {
         Ok(Stmt::Break { token: break_ })
     }
 
+    fn continue_statement(&mut self) -> Result<Stmt, ParseError> {
+        let continue_ = self.require_consume(TokenType::Continue, "Expect 'continue'")?;
+        self.require_consume(TokenType::SemiColon, "Expect ';' after continue")?;
+        Ok(Stmt::Continue { token: continue_ })
+    }
+
     fn return_statement(&mut self) -> Result<Stmt, ParseError> {
         let return_ = self.require_consume(TokenType::Return, "Expect 'return'")?;
         let mut return_value = None;
@@ -271,7 +475,7 @@ impl Parser {
             return_value,
         })
     }
-    fn block(&mut self) -> Result<Box<Vec<Stmt>>, ParseError> {
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         // consume { token
         self.consume_token();
 
@@ -283,27 +487,80 @@ impl Parser {
 
         // only errors if the self.is_done() causes the loop to terminate, i.e unclosed brace
         self.require_consume(TokenType::RightBrace, "Expect '}' to close a block")?;
-        Ok(Box::new(statements))
+        Ok(statements)
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
         let expression = self.expression()?;
+        if self.match_next_token(&[TokenType::Comma]) {
+            return self.multi_assignment(expression);
+        }
         self.require_consume(TokenType::SemiColon, "Expect ';' after expression")?;
         Ok(Stmt::Expression { expression })
     }
 
+    // a, b = b, a. first is the first target, already parsed
+    fn multi_assignment(&mut self, first: Expr) -> Result<Stmt, ParseError> {
+        let mut targets = vec![first];
+        while self.match_next_token(&[TokenType::Comma]) {
+            self.consume_token();
+            // not a full expression, which would take the = for an ordinary assignment
+            targets.push(self.ternary()?);
+        }
+        let equals = self.require_consume(TokenType::Equal, "Expect '=' after assignment targets")?;
+        for target in &targets {
+            if !matches!(target, Expr::Variable { .. } | Expr::Get { .. } | Expr::Index { .. }) {
+                self.error(&equals, "Invalid assignment target.");
+            }
+        }
+
+        let mut values = vec![self.expression()?];
+        while self.match_next_token(&[TokenType::Comma]) {
+            self.consume_token();
+            values.push(self.expression()?);
+        }
+        if values.len() != targets.len() {
+            self.error(
+                &equals,
+                &format!("Expected {} values to assign, found {}.", targets.len(), values.len()),
+            );
+        }
+        self.require_consume(TokenType::SemiColon, "Expect ';' after assignment")?;
+        Ok(Stmt::MultiAssign {
+            equals,
+            targets,
+            values,
+        })
+    }
+
     fn expression(&mut self) -> Result<Expr, ParseError> {
         self.assignment()
     }
 
     fn assignment(&mut self) -> Result<Expr, ParseError> {
-        let expr = self.or()?;
-
-        if self.match_next_token(&[TokenType::Equal]) {
+        let expr = self.ternary()?;
+
+        if self.match_next_token(&[
+            TokenType::Equal,
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+        ]) {
             let equals = self.consume_token().unwrap();
+            let operator = Parser::compound_operator(&equals);
             let value = self.assignment()?;
 
             if let Expr::Variable { name } = expr {
+                // reading a variable has no side effects, so x += 1 can simply become x = x + 1
+                let value = match operator {
+                    Some(operator) => Expr::Binary {
+                        left: Box::new(Expr::Variable { name: name.clone() }),
+                        right: Box::new(value),
+                        operator,
+                    },
+                    None => value,
+                };
                 return Ok(Expr::Assign {
                     name,
                     value: Box::new(value),
@@ -313,6 +570,37 @@ impl Parser {
                     object,
                     name,
                     value: Box::new(value),
+                    operator,
+                });
+            } else if let Expr::Index {
+                object,
+                bracket,
+                index,
+            } = expr
+            {
+                return Ok(Expr::IndexSet {
+                    object,
+                    bracket,
+                    index,
+                    value: Box::new(value),
+                    operator,
+                });
+            } else if let Expr::Slice {
+                object,
+                bracket,
+                start,
+                end,
+            } = expr
+            {
+                if operator.is_some() {
+                    self.error(&equals, "Can't use compound assignment on a slice.");
+                }
+                return Ok(Expr::SliceSet {
+                    object,
+                    bracket,
+                    start,
+                    end,
+                    value: Box::new(value),
                 });
             }
 
@@ -321,6 +609,40 @@ impl Parser {
         Ok(expr)
     }
 
+    // the binary operator a compound assignment applies, e.g. + for +=. None for plain =
+    fn compound_operator(equals: &Token) -> Option<Token> {
+        let (token_type, raw) = match equals.token_type {
+            TokenType::PlusEqual => (TokenType::Plus, "+"),
+            TokenType::MinusEqual => (TokenType::Minus, "-"),
+            TokenType::StarEqual => (TokenType::Star, "*"),
+            TokenType::SlashEqual => (TokenType::Slash, "/"),
+            _ => return None,
+        };
+        Some(Token {
+            token_type,
+            raw: raw.to_string(),
+            ..equals.clone()
+        })
+    }
+
+    fn ternary(&mut self) -> Result<Expr, ParseError> {
+        let condition = self.or()?;
+
+        if self.match_next_token(&[TokenType::Question]) {
+            self.consume_token();
+            let then_branch = self.expression()?;
+            self.require_consume(TokenType::Colon, "Expect ':' after then branch of conditional expression")?;
+            // recursing here rather than looping makes it right associative
+            let else_branch = self.ternary()?;
+            return Ok(Expr::Ternary {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            });
+        }
+        Ok(condition)
+    }
+
     fn or(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.and()?;
 
@@ -354,9 +676,73 @@ impl Parser {
     }
 
     fn equality(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.comparison()?;
+        let mut expr = self.bit_or()?;
 
         while self.match_next_token(&[TokenType::BangEqual, TokenType::EqualEqual]) {
+            let operator = self.consume_token().unwrap();
+            let right = self.bit_or()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                right: Box::new(right),
+                operator,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.bit_xor()?;
+
+        while self.match_next_token(&[TokenType::Pipe]) {
+            let operator = self.consume_token().unwrap();
+            let right = self.bit_xor()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                right: Box::new(right),
+                operator,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.bit_and()?;
+
+        while self.match_next_token(&[TokenType::Caret]) {
+            let operator = self.consume_token().unwrap();
+            let right = self.bit_and()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                right: Box::new(right),
+                operator,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.shift()?;
+
+        while self.match_next_token(&[TokenType::Ampersand]) {
+            let operator = self.consume_token().unwrap();
+            let right = self.shift()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                right: Box::new(right),
+                operator,
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn shift(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.comparison()?;
+
+        while self.match_next_token(&[TokenType::LessLess, TokenType::GreaterGreater]) {
             let operator = self.consume_token().unwrap();
             let right = self.comparison()?;
             expr = Expr::Binary {
@@ -434,6 +820,18 @@ impl Parser {
         }
     }
 
+    // a call argument or list element, which may be a list spread in place with ...
+    fn spreadable(&mut self) -> Result<Expr, ParseError> {
+        if !self.match_next_token(&[TokenType::Ellipsis]) {
+            return self.expression();
+        }
+        let ellipsis = self.consume_token().unwrap();
+        Ok(Expr::Spread {
+            ellipsis,
+            expression: Box::new(self.expression()?),
+        })
+    }
+
     fn call(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.primary()?;
 
@@ -443,24 +841,30 @@ impl Parser {
                 let left_paren = self.consume_token().unwrap();
                 // it's a function call
                 let mut arguments = vec![];
+                let mut too_many = None;
                 while !self.match_next_token(&[TokenType::RightParen]) {
                     // still have args
-                    arguments.push(self.expression()?);
-                    if arguments.len() > LOX_MAX_ARGUMENT_COUNT {
-                        self.error(&left_paren, "Exceeded max argument count");
+                    arguments.push(self.spreadable()?);
+                    if self.just_exceeded_max_args(arguments.len()) {
+                        too_many = Some(self.error(&left_paren, "Exceeded max argument count"));
                     }
                     if self.match_next_token(&[TokenType::RightParen]) {
                         break;
                     }
                     self.require_consume(TokenType::Comma, "Expect arguments are comma seperated")?;
                 }
+                let paren = self.require_consume(
+                    TokenType::RightParen,
+                    "Expect ')' closing function call",
+                )?;
+                // as with parameters, only fail once the call is closed
+                if let Some(err) = too_many {
+                    return Err(err);
+                }
                 expr = Expr::Call {
                     callee: Box::new(expr),
-                    paren: self.require_consume(
-                        TokenType::RightParen,
-                        "Expect ')' closing function call",
-                    )?,
-                    arguments: Box::new(arguments),
+                    paren,
+                    arguments,
                 };
             } else if self.match_next_token(&[TokenType::Dot]) {
                 // it's a instance access
@@ -474,6 +878,37 @@ impl Parser {
                     object: Box::new(expr),
                     name,
                 };
+            } else if self.match_next_token(&[TokenType::LeftBracket]) {
+                // it's an index into a list or string, or a slice of one
+                self.consume_token();
+                let index = if self.match_next_token(&[TokenType::Colon]) {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                if self.match_next_token(&[TokenType::Colon]) {
+                    self.consume_token();
+                    let end = if self.match_next_token(&[TokenType::RightBracket]) {
+                        None
+                    } else {
+                        Some(Box::new(self.expression()?))
+                    };
+                    let bracket = self.require_consume(TokenType::RightBracket, "Expect ']' after slice")?;
+                    expr = Expr::Slice {
+                        object: Box::new(expr),
+                        bracket,
+                        start: index.map(Box::new),
+                        end,
+                    };
+                    continue;
+                }
+                let bracket = self.require_consume(TokenType::RightBracket, "Expect ']' after index")?;
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    // only a slice can leave out its start
+                    index: Box::new(index.unwrap()),
+                };
             } else {
                 break;
             }
@@ -484,31 +919,20 @@ impl Parser {
 
     fn primary(&mut self) -> Result<Expr, ParseError> {
         match self.consume_token().unwrap() {
-            Token {
-                token_type: TokenType::False,
-                ..
-            } => Ok(Expr::Literal {
-                value: LoxType::Bool(false),
-            }),
-            Token {
-                token_type: TokenType::True,
-                ..
-            } => Ok(Expr::Literal {
-                value: LoxType::Bool(true),
-            }),
-            Token {
-                token_type: TokenType::Nil,
-                ..
-            } => Ok(Expr::Literal {
-                value: LoxType::Nil,
-            }),
-            Token {
-                token_type: TokenType::Number,
-                raw,
-                ..
-            } => Ok(Expr::Literal {
-                value: LoxType::Number(raw.parse::<f32>().unwrap()),
-            }),
+            t if matches!(
+                t.token_type,
+                TokenType::False | TokenType::True | TokenType::Nil | TokenType::Number | TokenType::Strang
+            ) =>
+            {
+                let value = match t.token_type {
+                    TokenType::False => LoxType::Bool(false),
+                    TokenType::True => LoxType::Bool(true),
+                    TokenType::Nil => LoxType::Nil,
+                    TokenType::Number => LoxType::Number(t.raw.parse::<f64>().unwrap()),
+                    _ => LoxType::Strang(t.raw.clone()),
+                };
+                Ok(Expr::Literal { value, token: t })
+            }
             Token {
                 token_type: TokenType::LeftParen,
                 ..
@@ -520,20 +944,60 @@ impl Parser {
                     expression: Box::new(expr),
                 })
             }
-            Token {
-                token_type: TokenType::Strang,
-                raw,
-                ..
-            } => Ok(Expr::Literal {
-                value: LoxType::Strang(raw),
-            }),
+            bracket if bracket.token_type == TokenType::LeftBracket => {
+                let mut elements = vec![];
+                // a trailing comma before the ] is allowed
+                while !self.match_next_token(&[TokenType::RightBracket, TokenType::EOF]) {
+                    elements.push(self.spreadable()?);
+                    if self.match_next_token(&[TokenType::RightBracket]) {
+                        break;
+                    }
+                    self.require_consume(TokenType::Comma, "Expect list elements are comma seperated")?;
+                }
+                self.require_consume(TokenType::RightBracket, "Expect ']' closing list")?;
+
+                Ok(Expr::ArrayLiteral { bracket, elements })
+            }
+            // blocks are statements, so a brace in an expression can only open a map
+            brace if brace.token_type == TokenType::LeftBrace => {
+                let mut entries = vec![];
+                while !self.match_next_token(&[TokenType::RightBrace, TokenType::EOF]) {
+                    let key = self.expression()?;
+                    self.require_consume(TokenType::Colon, "Expect ':' between map key and value")?;
+                    let value = self.expression()?;
+                    entries.push((key, value));
+                    if self.match_next_token(&[TokenType::RightBrace]) {
+                        break;
+                    }
+                    self.require_consume(TokenType::Comma, "Expect map entries are comma seperated")?;
+                }
+                self.require_consume(TokenType::RightBrace, "Expect '}' closing map")?;
+
+                Ok(Expr::MapLiteral { brace, entries })
+            }
             t if t.token_type == TokenType::Identifier => Ok(Expr::Variable { name: t }),
+            t if t.token_type == TokenType::This => Ok(Expr::This { keyword: t }),
+            // a named function is a declaration, so funct in an expression is always anonymous
+            keyword if keyword.token_type == TokenType::Funct => {
+                self.require_consume(TokenType::LeftParen, "Expect '(' after 'funct' in anonymous function")?;
+                let (parameters, body) = self.parameters_and_body()?;
+                Ok(Expr::Lambda {
+                    keyword,
+                    parameters,
+                    body,
+                })
+            }
+            t if t.token_type == TokenType::Super => {
+                self.require_consume(TokenType::Dot, "Expect '.' after 'super'")?;
+                let method = self.require_consume(TokenType::Identifier, "Expect superclass method name after 'super.'")?;
+                Ok(Expr::Super { keyword: t, method })
+            }
             t => Err(self.error(&t, "Expected expression")),
         }
     }
 
     fn match_next_token(&mut self, types: &[TokenType]) -> bool {
-        match self.tokens.peek() {
+        match self.peek_ahead(0) {
             None => false,
             Some(t) => types.contains(&t.token_type),
         }
@@ -552,40 +1016,57 @@ impl Parser {
     }
 
     fn error(&self, token: &Token, message: &str) -> ParseError {
-        println!(
-            "parser: {} caused by {:?}, at line {} column {}",
-            message, token.token_type, token.line, token.column
-        );
-        lox::report_error();
+        if token.token_type == TokenType::EOF {
+            self.error_at_end.set(true);
+        } else {
+            self.error_before_end.set(true);
+        }
+        self.reporter.report_error(Diagnostic::new(
+            Phase::Parse,
+            format!("{} caused by {:?}", message, token.token_type),
+            token.line,
+            token.column,
+        )
+        .starting_at(token));
         ParseError
     }
 
+    // skips to the start of the next statement, just past a ';' or at a keyword opening one
     fn synchronize(&mut self) {
         while !self.is_done()
-            && !self.match_next_token(&vec![
-                TokenType::EOF,
-                TokenType::SemiColon,
+            && self.previous != Some(TokenType::SemiColon)
+            && !self.match_next_token(&[TokenType::EOF,
                 TokenType::Class,
                 TokenType::Funct,
                 TokenType::Var,
+                TokenType::Val,
                 TokenType::For,
                 TokenType::If,
                 TokenType::While,
                 TokenType::Print,
-                TokenType::Return,
-            ])
+                TokenType::Return])
         {
             self.consume_token();
         }
     }
 
     pub fn is_done(&mut self) -> bool {
-        match self.tokens.peek() {
+        match self.peek_ahead(0) {
             None => true,
             Some(tok) => tok.token_type == TokenType::EOF,
         }
     }
 
+    // parses source consisting of exactly one expression
+    pub fn parse_expression(&mut self) -> Option<Expr> {
+        let expr = self.expression().ok()?;
+        if self.is_done() {
+            Some(expr)
+        } else {
+            None
+        }
+    }
+
     pub fn parse(&mut self) -> Vec<Stmt> {
         let mut statements = Vec::new();
         while !self.is_done() {
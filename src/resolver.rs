@@ -1,22 +1,56 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
-use crate::{common::Token, expr, interpreter::Interpreter, lox, stmt};
+use crate::{
+    common::{LoxType, Token, TokenType},
+    expr,
+    interpreter::Interpreter,
+    lox::{Diagnostic, ErrorReporter, Phase},
+    stmt,
+};
 
-pub struct Resolver {
+pub struct Resolver<'a> {
     interpreter: Rc<RefCell<Interpreter>>,
     scopes: Vec<HashMap<String, bool>>,
-    current_scope: ScopeType,
+    // alongside each scope, what's known about each local declared in it. `this` and
+    // `super` never go in, so are never warned about
+    locals: Vec<HashMap<String, Local>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    in_loop: bool,
+    // the pure function being resolved, if any, and the index of its first scope
+    pure_function: Option<(Token, usize)>,
+    // names of the pure functions declared so far, which pure functions may call
+    pure_functions: HashSet<String>,
+    // also warn about function parameters that are never used
+    warn_unused_parameters: bool,
+    reporter: &'a ErrorReporter,
 }
 
-impl Resolver {
-    pub fn new(interpreter: Rc<RefCell<Interpreter>>) -> Self {
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: Rc<RefCell<Interpreter>>, reporter: &'a ErrorReporter) -> Self {
         Self {
             interpreter,
             scopes: vec![],
-            current_scope: ScopeType::None,
+            locals: vec![],
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            in_loop: false,
+            pure_function: None,
+            pure_functions: HashSet::new(),
+            warn_unused_parameters: false,
+            reporter,
         }
     }
 
+    pub fn with_unused_parameter_warnings(mut self, warn: bool) -> Self {
+        self.warn_unused_parameters = warn;
+        self
+    }
+
     fn resolve_statement(&mut self, stmt: &stmt::Stmt) -> Result<(), ResolverError> {
         stmt::Visitor::visit_stmt(self, stmt)
     }
@@ -27,14 +61,51 @@ impl Resolver {
 
     fn begin_scope(&mut self) {
         self.scopes.push(HashMap::<String, bool>::new());
+        self.locals.push(HashMap::new());
     }
 
     fn end_scope(&mut self) {
         self.scopes.pop();
+        let locals = self.locals.pop().unwrap_or_default();
+
+        // in the order they were declared, so the warnings are the same from run to run
+        let mut unused: Vec<Local> = locals.into_values().filter(|local| !local.used).collect();
+        unused.sort_by_key(|local| (local.name.line, local.name.column));
+        for local in unused {
+            let kind = if local.parameter { "Parameter" } else { "Local variable" };
+            self.warning(&local.name, &format!("{} '{}' is never used", kind, local.name.raw));
+        }
+    }
+
+    // loop variables, and usually parameters, are there to be filled in so aren't warned about
+    fn mark_used(&mut self, name: &Token) {
+        if let Some(local) = self.locals.last_mut().and_then(|locals| locals.get_mut(&name.raw)) {
+            local.used = true;
+        }
+    }
+
+    // unused parameters are only warned about when asked for, and never when their name
+    // starts with an underscore, the usual way of saying one's unused on purpose
+    fn mark_parameter(&mut self, name: &Token) {
+        if !self.warn_unused_parameters || name.raw.starts_with('_') {
+            self.mark_used(name);
+        } else if let Some(local) = self.locals.last_mut().and_then(|locals| locals.get_mut(&name.raw)) {
+            local.parameter = true;
+        }
+    }
+
+    // declared with val, so can't be assigned to
+    fn mark_constant(&mut self, name: &Token) {
+        if let Some(local) = self.locals.last_mut().and_then(|locals| locals.get_mut(&name.raw)) {
+            local.constant = true;
+        }
     }
 
     fn declare(&mut self, name: &Token) {
+        // whatever the name held before, it isn't that pure function any more
+        self.pure_functions.remove(&name.raw);
         if self.scopes.is_empty() {
+            self.check_builtin(name);
             return;
         }
 
@@ -44,7 +115,18 @@ impl Resolver {
             .unwrap()
             .insert(name.raw.to_string(), false)
         {
-            None => {}
+            None => {
+                let local = Local {
+                    name: name.clone(),
+                    used: false,
+                    constant: false,
+                    parameter: false,
+                };
+                self.locals
+                    .last_mut()
+                    .unwrap()
+                    .insert(name.raw.to_string(), local);
+            }
             Some(_) => {
                 // variable name already declared in this scope.
                 self.error(name.clone(), "Already a variable with this name in scope");
@@ -63,9 +145,30 @@ impl Resolver {
             .insert(name.raw.to_string(), true);
     }
 
+    // name being assigned to, by an assignment or as one of a multiple assignment's targets
+    fn resolve_assignment(&mut self, name: &Token) -> Result<(), ResolverError> {
+        self.check_pure(name, true)?;
+        if !self.scopes.iter().any(|scope| scope.contains_key(&name.raw)) {
+            self.check_builtin(name);
+        }
+        // globals are left to the interpreter, a later file could redeclare them
+        let innermost = self.scopes.iter().rposition(|scope| scope.contains_key(&name.raw));
+        if innermost
+            .and_then(|i| self.locals[i].get(&name.raw))
+            .is_some_and(|local| local.constant)
+        {
+            return Err(self.error(name.clone(), "Can't assign to a constant."));
+        }
+        self.resolve_local(name.clone())
+    }
+
     fn resolve_local(&mut self, token: Token) -> Result<(), ResolverError> {
-        for (i, scope) in self.scopes.iter().enumerate() {
+        // innermost first, so a shadowing local is found before the one it shadows
+        for (i, scope) in self.scopes.iter().enumerate().rev() {
             if scope.contains_key(&token.raw) {
+                if let Some(local) = self.locals[i].get_mut(&token.raw) {
+                    local.used = true;
+                }
                 self.interpreter
                     .borrow_mut()
                     .resolve(token, self.scopes.len() - 1 - i);
@@ -75,26 +178,109 @@ impl Resolver {
         Ok(())
     }
 
+    fn resolve_function(
+        &mut self,
+        parameters: &[Token],
+        body: &[stmt::Stmt],
+        function_type: FunctionType,
+    ) -> Result<(), ResolverError> {
+        let enclosing_function = self.current_function;
+        let enclosing_in_loop = self.in_loop;
+        self.current_function = function_type;
+        // loops outside the function can't be broken out of from inside it
+        self.in_loop = false;
+
+        self.begin_scope();
+
+        for param in parameters {
+            self.declare(param);
+            self.define(param);
+            self.mark_parameter(param);
+        }
+
+        let result = self.resolve_block(body);
+
+        self.end_scope();
+        self.current_function = enclosing_function;
+        self.in_loop = enclosing_in_loop;
+
+        result
+    }
+
+    // the statements of a block or function body, warning about the first that can never
+    // run because it follows a return, break or continue
+    fn resolve_block(&mut self, statements: &[stmt::Stmt]) -> Result<(), ResolverError> {
+        let exit = statements.iter().position(|stmt| {
+            matches!(
+                stmt,
+                stmt::Stmt::Return { .. } | stmt::Stmt::Break { .. } | stmt::Stmt::Continue { .. }
+            )
+        });
+        if let Some(exit) = exit {
+            if let Some(unreachable) = statements.get(exit + 1) {
+                let exit = statements[exit].token();
+                self.warning(unreachable.token(), &format!("Code after {} is unreachable", exit.raw));
+            }
+        }
+
+        statements.iter().try_for_each(|stmt| self.resolve_statement(stmt))
+    }
+
+    // pure functions may only touch their own variables, though they can read other pure
+    // functions to call them. Natives are all off limits, being no different to outside state
+    fn check_pure(&self, name: &Token, assigning: bool) -> Result<(), ResolverError> {
+        let start = match &self.pure_function {
+            Some((_, start)) => *start,
+            None => return Ok(()),
+        };
+        let own = self.scopes[start..]
+            .iter()
+            .any(|scope| scope.contains_key(&name.raw));
+        if own || (!assigning && self.pure_functions.contains(&name.raw)) {
+            return Ok(());
+        }
+        Err(self.error(
+            name.clone(),
+            "A pure function can only use its own variables and other pure functions",
+        ))
+    }
+
+    // globals sharing a protected native's name would replace it
+    fn check_builtin(&self, name: &Token) {
+        if self.interpreter.borrow().is_protected_builtin(&name.raw) {
+            self.error(name.clone(), "Can't redefine a built-in function");
+        }
+    }
+
+    fn warning(&self, token: &Token, message: &str) {
+        self.reporter.report_warning(Diagnostic::warning(
+            Phase::Resolve,
+            message.to_string(),
+            token.line,
+            token.column,
+        )
+        .starting_at(token));
+    }
+
     fn error(&self, token: Token, message: &str) -> ResolverError {
-        println!(
-            "Resolver: {} caused by {} at line {} column {}",
-            message, token.raw, token.line, token.column
-        );
-        lox::report_error();
+        self.reporter.report_error(Diagnostic::new(
+            Phase::Resolve,
+            format!("{} caused by {}", message, token.raw),
+            token.line,
+            token.column,
+        )
+        .starting_at(&token));
         ResolverError::new(token, message.to_string())
     }
 
     pub fn resolve(&mut self, statements: &[stmt::Stmt]) {
         for stmt in statements {
-            match self.resolve_statement(stmt) {
-                Err(_) => {}
-                Ok(_) => {}
-            }
+            if self.resolve_statement(stmt).is_err() {}
         }
     }
 }
 
-impl expr::Visitor<(), ResolverError> for Resolver {
+impl expr::Visitor<(), ResolverError> for Resolver<'_> {
     fn visit_expr(&mut self, expr: &expr::Expr) -> Result<(), ResolverError> {
         match expr {
             expr::Expr::Variable { name } => {
@@ -106,14 +292,14 @@ impl expr::Visitor<(), ResolverError> for Resolver {
                         "Cannot use a variable in it's own initializer",
                     ))
                 } else {
+                    self.check_pure(name, false)?;
                     self.resolve_local(name.clone())?;
                     Ok(())
                 }
             }
             expr::Expr::Assign { name, value } => {
                 self.resolve_expr(value)?;
-                self.resolve_local(name.clone())?;
-                Ok(())
+                self.resolve_assignment(name)
             }
             expr::Expr::Binary { left, right, .. } => {
                 self.resolve_expr(left)?;
@@ -124,20 +310,103 @@ impl expr::Visitor<(), ResolverError> for Resolver {
                 callee, arguments, ..
             } => {
                 self.resolve_expr(callee)?;
-                for arg in (*arguments).iter() {
+                for arg in arguments.iter() {
                     self.resolve_expr(arg)?;
                 }
                 Ok(())
             }
             expr::Expr::Grouping { expression } => self.resolve_expr(expression),
             expr::Expr::Literal { .. } => Ok(()),
+            expr::Expr::Index { object, index, .. } => {
+                self.resolve_expr(object)?;
+                self.resolve_expr(index)?;
+                Ok(())
+            }
+            expr::Expr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.resolve_expr(object)?;
+                self.resolve_expr(index)?;
+                self.resolve_expr(value)?;
+                Ok(())
+            }
+            expr::Expr::Slice {
+                object, start, end, ..
+            } => {
+                self.resolve_expr(object)?;
+                for bound in [start, end].into_iter().flatten() {
+                    self.resolve_expr(bound)?;
+                }
+                Ok(())
+            }
+            expr::Expr::SliceSet {
+                object,
+                start,
+                end,
+                value,
+                ..
+            } => {
+                self.resolve_expr(object)?;
+                for bound in [start, end].into_iter().flatten() {
+                    self.resolve_expr(bound)?;
+                }
+                self.resolve_expr(value)?;
+                Ok(())
+            }
+            expr::Expr::MapLiteral { entries, .. } => {
+                for (key, value) in entries {
+                    self.resolve_expr(key)?;
+                    self.resolve_expr(value)?;
+                }
+                Ok(())
+            }
+            expr::Expr::Lambda {
+                parameters, body, ..
+            } => self.resolve_function(parameters, body, FunctionType::Function),
+            expr::Expr::ArrayLiteral { elements, .. } => {
+                for element in elements {
+                    self.resolve_expr(element)?;
+                }
+                Ok(())
+            }
+            expr::Expr::Spread { expression, .. } => self.resolve_expr(expression),
             expr::Expr::Logical { left, right, .. } => {
                 self.resolve_expr(left)?;
                 self.resolve_expr(right)?;
                 Ok(())
             }
             expr::Expr::Unary { right, .. } => self.resolve_expr(right),
+            expr::Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expr(condition)?;
+                self.resolve_expr(then_branch)?;
+                self.resolve_expr(else_branch)?;
+                Ok(())
+            }
             expr::Expr::Get { object, .. } => self.resolve_expr(object),
+            expr::Expr::This { keyword } => {
+                if let ClassType::None = self.current_class {
+                    Err(self.error(keyword.clone(), "Can't use 'this' outside of a class."))
+                } else {
+                    self.resolve_local(keyword.clone())
+                }
+            }
+            expr::Expr::Super { keyword, .. } => match self.current_class {
+                ClassType::None => {
+                    Err(self.error(keyword.clone(), "Can't use 'super' outside of a class."))
+                }
+                ClassType::Class => Err(self.error(
+                    keyword.clone(),
+                    "Can't use 'super' in a class with no superclass.",
+                )),
+                ClassType::Subclass => self.resolve_local(keyword.clone()),
+            },
             expr::Expr::Set { object, value, .. } => {
                 self.resolve_expr(object)?;
                 self.resolve_expr(value)?;
@@ -147,19 +416,24 @@ impl expr::Visitor<(), ResolverError> for Resolver {
     }
 }
 
-impl stmt::Visitor<(), ResolverError> for Resolver {
+impl stmt::Visitor<(), ResolverError> for Resolver<'_> {
     fn visit_stmt(&mut self, stmt: &stmt::Stmt) -> Result<(), ResolverError> {
         match stmt {
-            stmt::Stmt::Block { statements } => {
+            stmt::Stmt::Block { statements, .. } => {
                 self.begin_scope();
-                for stmt in statements.iter() {
-                    self.resolve_statement(stmt)?;
-                }
+                self.resolve_block(statements)?;
                 self.end_scope();
                 Ok(())
             }
-            stmt::Stmt::Var { name, initializer } => {
+            stmt::Stmt::Var {
+                name,
+                initializer,
+                is_const,
+            } => {
                 self.declare(name);
+                if *is_const {
+                    self.mark_constant(name);
+                }
                 if let Some(init) = initializer {
                     self.resolve_expr(init)?;
                 }
@@ -170,35 +444,48 @@ impl stmt::Visitor<(), ResolverError> for Resolver {
                 name,
                 parameters,
                 body,
+                pure,
             } => {
                 self.declare(name);
                 self.define(name);
 
-                let enclosing_scope_type = self.current_scope;
-                self.current_scope = ScopeType::Function;
-
-                self.begin_scope();
-
-                for param in parameters {
-                    self.declare(param);
-                    self.define(param);
+                if !*pure {
+                    return self.resolve_function(parameters, body, FunctionType::Function);
                 }
-
-                for stmt in (*body).iter() {
-                    self.resolve_statement(stmt)?;
+                // added first so it can call itself
+                self.pure_functions.insert(name.raw.clone());
+                let enclosing_pure_function = self
+                    .pure_function
+                    .replace((name.clone(), self.scopes.len()));
+                let result = self.resolve_function(parameters, body, FunctionType::Function);
+                self.pure_function = enclosing_pure_function;
+                result
+            }
+            stmt::Stmt::Expression { expression } => self.resolve_expr(expression),
+            stmt::Stmt::MultiAssign { targets, values, .. } => {
+                for value in values {
+                    self.resolve_expr(value)?;
+                }
+                for target in targets {
+                    match target {
+                        expr::Expr::Variable { name } => self.resolve_assignment(name)?,
+                        other => self.resolve_expr(other)?,
+                    }
                 }
-
-                self.end_scope();
-                self.current_scope = enclosing_scope_type;
-
                 Ok(())
             }
-            stmt::Stmt::Expression { expression } => self.resolve_expr(expression),
             stmt::Stmt::If {
+                keyword,
                 condition,
                 then_branch,
                 else_branch,
             } => {
+                if let Some(value) = constant_value(condition) {
+                    self.warning(
+                        keyword,
+                        &format!("'if' condition is always {}", Interpreter::is_truthy(&value)),
+                    );
+                }
                 self.resolve_expr(condition)?;
                 self.resolve_statement(then_branch)?;
                 if let Some(b) = else_branch {
@@ -206,37 +493,124 @@ impl stmt::Visitor<(), ResolverError> for Resolver {
                 }
                 Ok(())
             }
+            stmt::Stmt::ForEach {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.resolve_expr(iterable)?;
+
+                let enclosing_in_loop = self.in_loop;
+                self.in_loop = true;
+                // the variable gets a fresh scope each time round, just outside the body
+                self.begin_scope();
+                self.declare(variable);
+                self.define(variable);
+                self.mark_used(variable);
+                self.resolve_statement(body)?;
+                self.end_scope();
+                self.in_loop = enclosing_in_loop;
+                Ok(())
+            }
             stmt::Stmt::While {
+                keyword,
                 condition,
                 then_branch,
+                increment,
                 finally_branch,
+                fresh_bindings,
             } => {
-                let enclosing_scope_type = self.current_scope;
-                self.current_scope = ScopeType::Loop;
+                // while (true) is the usual way to loop until a break, so isn't worth a warning
+                match constant_value(condition) {
+                    Some(LoxType::Bool(true)) | None => {}
+                    Some(value) => self.warning(
+                        keyword,
+                        &format!("loop condition is always {}", Interpreter::is_truthy(&value)),
+                    ),
+                }
+                let enclosing_in_loop = self.in_loop;
+                self.in_loop = true;
 
                 self.resolve_expr(condition)?;
+                // mirrors the environment holding each iteration's copies of the variables
+                if !fresh_bindings.is_empty() {
+                    self.begin_scope();
+                    for name in fresh_bindings {
+                        self.declare(name);
+                        self.define(name);
+                        self.mark_used(name);
+                    }
+                }
                 self.resolve_statement(then_branch)?;
+                if !fresh_bindings.is_empty() {
+                    self.end_scope();
+                }
+                if let Some(inc) = increment {
+                    self.resolve_expr(inc)?;
+                }
+
+                // finally runs once the loop is over, so it isn't part of the loop
+                self.in_loop = enclosing_in_loop;
                 if let Some(b) = finally_branch {
                     self.resolve_statement(b)?;
                 }
-
-                self.current_scope = enclosing_scope_type;
                 Ok(())
             }
-            stmt::Stmt::Print { expression } => self.resolve_expr(expression),
+            stmt::Stmt::Print { expression, .. } => {
+                // print keeps no token of its own, so point at the function instead
+                if let Some((name, _)) = &self.pure_function {
+                    return Err(self.error(name.clone(), "A pure function can't print"));
+                }
+                self.resolve_expr(expression)
+            }
+            stmt::Stmt::Assert {
+                condition, message, ..
+            } => {
+                self.resolve_expr(condition)?;
+                if let Some(message) = message {
+                    self.resolve_expr(message)?;
+                }
+                Ok(())
+            }
+            stmt::Stmt::Defer {
+                keyword,
+                expression,
+            } => {
+                if self.scopes.is_empty() {
+                    return Err(self.error(keyword.clone(), "Can only defer inside a block."));
+                }
+                self.resolve_expr(expression)
+            }
             stmt::Stmt::Break { token } => {
-                if let ScopeType::Loop = self.current_scope {
+                if self.in_loop {
                     Ok(())
                 } else {
                     Err(self.error(token.clone(), "Can only break from inside a loop."))
                 }
             }
+            stmt::Stmt::Continue { token } => {
+                if self.in_loop {
+                    Ok(())
+                } else {
+                    Err(self.error(token.clone(), "Can only continue from inside a loop."))
+                }
+            }
             stmt::Stmt::Return {
                 return_value,
                 token,
             } => {
-                if let ScopeType::Function = self.current_scope {
+                if let FunctionType::Function | FunctionType::Method | FunctionType::Initializer =
+                    self.current_function
+                {
                     if let Some(val) = return_value {
+                        // init always gives back the instance
+                        if let FunctionType::Initializer = self.current_function {
+                            return Err(self.error(
+                                token.clone(),
+                                "Can't return a value from an initializer.",
+                            ));
+                        }
                         self.resolve_expr(val)?;
                     }
                     Ok(())
@@ -244,15 +618,87 @@ impl stmt::Visitor<(), ResolverError> for Resolver {
                     Err(self.error(token.clone(), "Can only return from a function."))
                 }
             }
-            stmt::Stmt::Class { name, .. } => {
+            stmt::Stmt::Class {
+                name,
+                superclass,
+                methods,
+            } => {
                 self.declare(name);
                 self.define(name);
-                Ok(())
-            },
+
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+
+                if let Some(superclass) = superclass {
+                    if let expr::Expr::Variable { name: super_name } = superclass {
+                        if super_name.raw == name.raw {
+                            self.current_class = enclosing_class;
+                            return Err(self.error(
+                                super_name.clone(),
+                                "A class can't inherit from itself.",
+                            ));
+                        }
+                    }
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expr(superclass)?;
+
+                    // mirrors the environment holding `super` that the interpreter wraps methods in
+                    self.begin_scope();
+                    self.scopes
+                        .last_mut()
+                        .unwrap()
+                        .insert("super".to_string(), true);
+                }
+
+                // methods close over a scope holding `this`, matching the environment bind creates
+                self.begin_scope();
+                self.scopes
+                    .last_mut()
+                    .unwrap()
+                    .insert("this".to_string(), true);
+
+                let result = methods.iter().try_for_each(|method| match method {
+                    stmt::Stmt::Function {
+                        name,
+                        parameters,
+                        body,
+                        ..
+                    } => {
+                        let function_type = if name.raw == "init" {
+                            FunctionType::Initializer
+                        } else {
+                            FunctionType::Method
+                        };
+                        self.resolve_function(parameters, body, function_type)
+                    }
+                    other => self.resolve_statement(other),
+                });
+
+                self.end_scope();
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+                self.current_class = enclosing_class;
+
+                result
+            }
         }
     }
 }
 
+// the value an expression always has, if it's known without running anything
+fn constant_value(expr: &expr::Expr) -> Option<LoxType> {
+    match expr {
+        expr::Expr::Literal { value, .. } => Some(value.clone()),
+        expr::Expr::Grouping { expression } => constant_value(expression),
+        expr::Expr::Unary { operator, right } if operator.token_type == TokenType::Bang => {
+            let value = constant_value(right)?;
+            Some(LoxType::Bool(!Interpreter::is_truthy(&value)))
+        }
+        _ => None,
+    }
+}
+
 pub struct ResolverError {
     pub token: Token,
     pub message: String,
@@ -264,9 +710,24 @@ impl ResolverError {
     }
 }
 
+struct Local {
+    name: Token,
+    used: bool,
+    constant: bool,
+    parameter: bool,
+}
+
 #[derive(Clone, Copy)]
-enum ScopeType {
+enum FunctionType {
     None,
     Function,
-    Loop,
+    Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
 }
@@ -1,10 +1,12 @@
 use crate::common::Token;
 use crate::expr::Expr;
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
     Block {
-        statements: Box<Vec<Stmt>>,
+        // the {, or for the block a for loop's initializer is wrapped in the for
+        brace: Token,
+        statements: Vec<Stmt>,
     },
 
     Expression {
@@ -12,44 +14,110 @@ pub enum Stmt {
     },
 
     If {
+        keyword: Token,
         condition: Expr,
         then_branch: Box<Stmt>,
         else_branch: Option<Box<Stmt>>,
     },
 
     While {
+        // the while, or for a desugared for loop the for
+        keyword: Token,
         condition: Expr,
         then_branch: Box<Stmt>,
-        finally_branch: Option<Box<Stmt>>
+        // run after every iteration, including ones cut short by continue
+        increment: Option<Expr>,
+        // run once the condition is false, but not when the body breaks out
+        finally_branch: Option<Box<Stmt>>,
+        // a for loop's variables. The body sees a fresh copy of each every iteration, so
+        // closures made in it keep that iteration's value
+        fresh_bindings: Vec<Token>,
+    },
+
+    // for (variable in iterable) body
+    ForEach {
+        keyword: Token,
+        variable: Token,
+        iterable: Expr,
+        body: Box<Stmt>,
     },
 
     Print {
+        keyword: Token,
+        expression: Expr,
+    },
+
+    Assert {
+        keyword: Token,
+        condition: Expr,
+        message: Option<Expr>,
+    },
+
+    // runs expression when the enclosing block is left, however it's left
+    Defer {
+        keyword: Token,
         expression: Expr,
     },
 
     Break {
         token: Token
     },
+
+    Continue {
+        token: Token
+    },
     
     Return {
         token: Token,
         return_value: Option<Expr>,
     },
 
+    // a, b = b, a. Every value is evaluated before any target is assigned, so this swaps
+    MultiAssign {
+        equals: Token,
+        // variables, properties and indexes, as many as there are values
+        targets: Vec<Expr>,
+        values: Vec<Expr>,
+    },
+
     Var {
         name: Token,
         initializer: Option<Expr>,
+        // declared with val, so it can't be assigned to
+        is_const: bool,
     },
 
     Function {
         name: Token,
         parameters: Vec<Token>,
-        body: Box<Vec<Stmt>>,
+        body: Vec<Stmt>,
+        // declared `pure funct`, so its results can be remembered
+        pure: bool,
     },
     
     Class {
         name: Token,
-        methods: Box<Vec<Stmt>>
+        superclass: Option<Expr>,
+        methods: Vec<Stmt>
+    }
+}
+
+impl Stmt {
+    // the first token found in a statement, for pointing at it
+    pub fn token(&self) -> &Token {
+        match self {
+            Stmt::Block { brace, .. } => brace,
+            Stmt::Expression { expression } => expression.token(),
+            Stmt::Print { keyword, .. }
+            | Stmt::If { keyword, .. }
+            | Stmt::While { keyword, .. }
+            | Stmt::ForEach { keyword, .. }
+            | Stmt::Assert { keyword, .. }
+            | Stmt::Defer { keyword, .. } => keyword,
+            Stmt::Break { token } | Stmt::Continue { token } | Stmt::Return { token, .. } => token,
+            Stmt::Var { name, .. } | Stmt::Function { name, .. } | Stmt::Class { name, .. } => name,
+            Stmt::MultiAssign { equals, targets, .. } => targets.first().map_or(equals, Expr::token),
+        }
     }
 }
 
@@ -0,0 +1,83 @@
+use serde::Serialize;
+
+use crate::{
+    common::TokenType,
+    lexer::{Lexer, TokenSpan},
+    lox::{Diagnostic, ErrorReporter},
+};
+
+// one entry in the token stream handed to editors. Columns start at 1, end_column is one
+// past the token's last character
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum Entry {
+    Token {
+        #[serde(rename = "type")]
+        token_type: TokenType,
+        text: String,
+        line: u32,
+        end_line: u32,
+        start_column: u32,
+        end_column: u32,
+    },
+    // lexer errors sit in the stream where they happened, rather than stopping it
+    Error {
+        #[serde(rename = "type")]
+        entry_type: &'static str,
+        message: String,
+        line: u32,
+        column: u32,
+    },
+}
+
+impl Entry {
+    fn position(&self) -> (u32, u32) {
+        match self {
+            Entry::Token {
+                line, start_column, ..
+            } => (*line, *start_column),
+            Entry::Error { line, column, .. } => (*line, *column),
+        }
+    }
+}
+
+impl From<TokenSpan> for Entry {
+    fn from(span: TokenSpan) -> Self {
+        Entry::Token {
+            token_type: span.token.token_type,
+            text: span.token.raw,
+            line: span.start_line,
+            end_line: span.token.line,
+            start_column: span.start_column,
+            end_column: span.token.column,
+        }
+    }
+}
+
+impl From<Diagnostic> for Entry {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Entry::Error {
+            entry_type: "error",
+            message: diagnostic.message,
+            line: diagnostic.line,
+            column: diagnostic.column,
+        }
+    }
+}
+
+// the whole token stream of source as a JSON array, e.g. for `print 1;`
+// [{"type":"Print","text":"print","line":1,"end_line":1,"start_column":1,"end_column":6}, ...]
+pub fn tokens_json(source: &str) -> String {
+    let reporter = ErrorReporter::new();
+    let mut entries: Vec<Entry> = Lexer::new(source, &reporter)
+        .collect_token_spans()
+        .into_iter()
+        .map(Entry::from)
+        .collect();
+
+    entries.extend(reporter.take_diagnostics().into_iter().map(Entry::from));
+    // stable, so an error and a token at the same spot keep that order
+    entries.sort_by_key(Entry::position);
+
+    serde_json::to_string(&entries).expect("tokens always serialize")
+}
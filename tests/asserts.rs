@@ -0,0 +1,55 @@
+mod common;
+
+use common::{error, printed};
+
+#[test]
+fn assert_throws_passes_when_the_function_raises() {
+    let source = "print assert_throws(funct () { return 1 / 0; });\nprint assert_throws(funct () { return 1 / 0; }, \"divide by 0\");";
+    assert_eq!(printed(source), "nil\nnil\n");
+}
+
+#[test]
+fn assert_throws_fails_when_nothing_or_the_wrong_thing_is_raised() {
+    let none = error("assert_throws(funct () { return 1 / 1; });");
+    assert!(none.message.starts_with("expected an error but none occurred"), "{}", none.message);
+
+    let wrong = error("assert_throws(funct () { return 1 / 0; }, \"out of bounds\");");
+    assert!(
+        wrong.message.starts_with("expected an error containing out of bounds but got: "),
+        "{}",
+        wrong.message
+    );
+}
+
+#[test]
+fn assert_throws_needs_a_function_taking_no_arguments() {
+    // calling this with no arguments would raise, passing the assertion by accident
+    let takes_one = error("assert_throws(funct (x) { return x; });");
+    assert!(
+        takes_one.message.starts_with("assert_throws expects a function taking no arguments"),
+        "{}",
+        takes_one.message
+    );
+    let not_a_function = error("assert_throws(1);");
+    assert!(not_a_function.message.starts_with("assert_throws expects a function taking no arguments"));
+}
+
+#[test]
+fn assert_true_passes_on_truthy_values() {
+    assert_eq!(printed("print assert_true(1 < 2);\nassert_true(\"non-empty\", \"strings are truthy\");"), "nil\n");
+}
+
+#[test]
+fn assert_true_fails_on_falsy_values() {
+    let bare = error("assert_true(1 > 2);");
+    assert!(bare.message.starts_with("assertion failed caused by"), "{}", bare.message);
+
+    let with_message = error("assert_true(nil, \"nil is falsy\");");
+    assert!(
+        with_message.message.starts_with("assertion failed: nil is falsy caused by"),
+        "{}",
+        with_message.message
+    );
+
+    assert!(error("assert_true(true, 42);").message.contains("expects a string"));
+}
@@ -0,0 +1,35 @@
+use lox::{ast_graphviz::AstGraphviz, lox::parse_source};
+
+fn dot(source: &str) -> String {
+    AstGraphviz::new().render(&parse_source(source).expect("source parses"))
+}
+
+#[test]
+fn binary_nodes_hang_off_their_operator() {
+    assert_eq!(
+        dot("print 1 + 2 * 3;"),
+        "\
+digraph ast {
+  node0 [label=\"Program\"];
+  node1 [label=\"Print\"];
+  node2 [label=\"Binary +\"];
+  node3 [label=\"Literal 1\"];
+  node4 [label=\"Binary *\"];
+  node5 [label=\"Literal 2\"];
+  node6 [label=\"Literal 3\"];
+  node2 -> node3;
+  node4 -> node5;
+  node4 -> node6;
+  node2 -> node4;
+  node1 -> node2;
+  node0 -> node1;
+}
+"
+    );
+}
+
+#[test]
+fn quotes_and_backslashes_in_labels_are_escaped() {
+    let dot = dot("print \"a\\b\";");
+    assert!(dot.contains(r#"node2 [label="Literal \"a\\b\""];"#), "{}", dot);
+}
@@ -0,0 +1,74 @@
+use std::fs;
+
+use lox::{
+    ast_json::{ast_fingerprint, ast_json},
+    lox::parse_source,
+    token_json::tokens_json,
+};
+use serde_json::{json, Value};
+
+fn tree(source: &str) -> Value {
+    let statements = parse_source(source).expect("source parses");
+    serde_json::from_str(&ast_json(&statements)).unwrap()
+}
+
+#[test]
+fn var_with_binary_initializer() {
+    let position = |lexeme: &str, column: u32| json!({"lexeme": lexeme, "line": 1, "column": column});
+    assert_eq!(
+        tree("var x = 1 + 2;"),
+        json!([{
+            "type": "Var",
+            "name": position("x", 5),
+            "const": false,
+            "initializer": {
+                "type": "Binary",
+                "operator": position("+", 11),
+                "left": {"type": "Literal", "token": position("1", 9), "value": 1.0},
+                "right": {"type": "Literal", "token": position("2", 13), "value": 2.0},
+            },
+        }])
+    );
+}
+
+#[test]
+fn positions_match_the_token_dump() {
+    let source = "print \"two\nlines\";\nvar name = 10;";
+    let tokens: Value = serde_json::from_str(&tokens_json(source)).unwrap();
+    let start = |text: &str| {
+        let token = tokens
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|t| t["text"] == text)
+            .unwrap();
+        (token["line"].clone(), token["start_column"].clone())
+    };
+
+    let tree = tree(source);
+    let string = &tree[0]["expression"]["token"];
+    assert_eq!((string["line"].clone(), string["column"].clone()), start("two\nlines"));
+    let name = &tree[1]["name"];
+    assert_eq!((name["line"].clone(), name["column"].clone()), start("name"));
+}
+
+fn fingerprint(source: &str) -> String {
+    ast_fingerprint(&parse_source(source).expect("source parses"))
+}
+
+fn fingerprint_file(name: &str) -> String {
+    let path = format!("{}/test_files/fingerprint/{}", env!("CARGO_MANIFEST_DIR"), name);
+    fingerprint(&fs::read_to_string(path).unwrap())
+}
+
+#[test]
+fn layout_and_comments_leave_the_fingerprint_alone() {
+    assert_eq!(fingerprint_file("compact.lox"), fingerprint_file("spaced.lox"));
+}
+
+#[test]
+fn a_different_parse_changes_the_fingerprint() {
+    assert_ne!(fingerprint("print 1 + 2 * 3;"), fingerprint("print (1 + 2) * 3;"));
+    assert_ne!(fingerprint("var total = 1;"), fingerprint("var count = 1;"));
+    assert_eq!(fingerprint("print 1 + 2 * 3;"), fingerprint("print 1+2*3;"));
+}
@@ -0,0 +1,22 @@
+use lox::{ast_printer::AstPrinter, lox::parse_source};
+
+fn printed(source: &str) -> String {
+    AstPrinter {}.print_program(&parse_source(source).expect("source parses"))
+}
+
+#[test]
+fn nested_blocks_print_inside_their_parent() {
+    let source = "var a = 1;\n{\n  var b = a + 2;\n  {\n    print b * 3;\n  }\n  {}\n}\nprint a;\n";
+    assert_eq!(
+        printed(source),
+        "(var a = 1)\n(block (var b = (a + 2)) (block (print (b * 3))) (block))\n(print a)"
+    );
+}
+
+#[test]
+fn blocks_inside_control_flow_print_inside_it() {
+    assert_eq!(
+        printed("if (true) { while (false) { print 1; } } else {}"),
+        "(if true (block (while false (block (print 1)))) (block))"
+    );
+}
@@ -0,0 +1,54 @@
+use lox::{bytecode::compile, lox::parse_source};
+
+// the listing --dump-bytecode prints for source, one chunk after another
+fn listing(source: &str) -> String {
+    compile(&parse_source(source).expect("source parses"))
+        .into_iter()
+        .map(|chunk| chunk.map_or_else(|message| message + "\n", |chunk| chunk.disassemble()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn precedence_decides_the_instruction_order() {
+    assert_eq!(
+        listing("print 1 + 2 * 3;"),
+        "\
+== <script> ==
+0000    1 Constant 0 (1)
+0001    | Constant 1 (2)
+0002    | Constant 2 (3)
+0003    | Multiply
+0004    | Add
+0005    | Print
+0006    | Nil
+0007    | Return
+"
+    );
+}
+
+#[test]
+fn functions_get_their_own_chunk() {
+    assert_eq!(
+        listing("funct double(n) {\n  return n * 2;\n}\nprint double(4);"),
+        "\
+== <script> ==
+0000    1 Function 'double'
+0001    | DefineVariable 'double'
+0002    4 GetVariable 'double'
+0003    | Constant 0 (4)
+0004    | Call 1
+0005    | Print
+0006    | Nil
+0007    | Return
+
+== double ==
+0000    2 GetVariable 'n'
+0001    | Constant 0 (2)
+0002    | Multiply
+0003    | Return
+0004    | Nil
+0005    | Return
+"
+    );
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::capturing_interpreter;
+use lox::{
+    common::{LoxType, LOX_MAX_CALL_DEPTH},
+    lox::run_collecting,
+};
+
+#[test]
+fn recursion_through_a_native_callback_hits_the_call_depth_limit() {
+    let (interpreter, _) = capturing_interpreter();
+    let source = "funct down() {\n  return retry(down, 1);\n}\ndown();\n";
+
+    let errors = run_collecting(source, interpreter).unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    let expected = format!("Exceeded max call depth of {}", LOX_MAX_CALL_DEPTH);
+    assert!(errors[0].message.starts_with(&expected), "{}", errors[0].message);
+    assert_eq!(errors[0].line, 2);
+}
+
+#[test]
+fn plain_recursion_hits_the_call_depth_limit() {
+    let (interpreter, _) = capturing_interpreter();
+    let source = "funct down(n) {\n  return down(n + 1);\n}\ndown(0);\n";
+
+    let errors = run_collecting(source, interpreter).unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.starts_with("Exceeded max call depth"));
+}
+
+#[test]
+fn recursion_started_from_rust_hits_the_call_depth_limit() {
+    let (interpreter, _) = capturing_interpreter();
+    run_collecting("funct down(n) {\n  return down(n + 1);\n}\n", interpreter.clone()).unwrap();
+
+    let down = interpreter.borrow().get_global("down").unwrap();
+    let error = interpreter
+        .borrow_mut()
+        .call_value(down, vec![LoxType::Number(0.0)])
+        .unwrap_err();
+
+    assert!(error.message.starts_with("Exceeded max call depth"), "{}", error.message);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::capturing_interpreter;
+use lox::{common::LoxType, lox::run_collecting};
+
+#[test]
+fn calls_a_lox_function_fetched_from_globals() {
+    let (interpreter, output) = capturing_interpreter();
+    let source = "funct add(a, b) { print \"adding\"; return a + b; }";
+    run_collecting(source, interpreter.clone()).unwrap();
+
+    let add = interpreter.borrow().get_global("add").expect("add is defined");
+    let result = interpreter
+        .borrow_mut()
+        .call_value(add, vec![LoxType::Number(2.0), LoxType::Number(3.0)])
+        .unwrap();
+
+    assert!(matches!(result, LoxType::Number(n) if n == 5.0));
+    assert_eq!(output.text(), "adding\n");
+}
+
+#[test]
+fn calling_with_the_wrong_number_of_arguments_is_an_error() {
+    let (interpreter, output) = capturing_interpreter();
+    run_collecting("funct add(a, b) { print \"adding\"; return a + b; }", interpreter.clone()).unwrap();
+
+    let add = interpreter.borrow().get_global("add").unwrap();
+    let error = interpreter
+        .borrow_mut()
+        .call_value(add, vec![LoxType::Number(2.0)])
+        .unwrap_err();
+
+    assert_eq!(error.message, "Expected 2 arguments, found 1");
+    // the function never ran
+    assert_eq!(output.text(), "");
+}
+
+#[test]
+fn get_global_is_none_for_undefined_names_and_call_value_rejects_non_callables() {
+    let (interpreter, _) = capturing_interpreter();
+    run_collecting("var answer = 42;", interpreter.clone()).unwrap();
+
+    assert!(interpreter.borrow().get_global("missing").is_none());
+    let answer = interpreter.borrow().get_global("answer").unwrap();
+    let error = interpreter.borrow_mut().call_value(answer, vec![]).unwrap_err();
+    assert!(error.message.contains("Only functions and classes may be called"));
+}
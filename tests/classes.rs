@@ -0,0 +1,65 @@
+mod common;
+
+use common::{error, printed};
+
+const PUPPY: &str = "class Animal {
+    meth speak(times) {
+        return this.name + \" makes a sound x\" + str(times);
+    }
+}
+class Dog < Animal {
+    meth speak(times) {
+        return this.name + \" barks x\" + str(times);
+    }
+}
+class Puppy < Dog {
+    meth speak(times) {
+        return this.name + \" yips x\" + str(times);
+    }
+}
+var p = Puppy();
+p.name = \"Rex\";
+";
+
+#[test]
+fn base_calls_a_grandparent_method_past_the_parents_override() {
+    let source = format!(
+        "{}print p.speak(1);\nprint base(p, \"speak\", [2]);\nprint base(p, \"speak\", [3], Animal);",
+        PUPPY
+    );
+    assert_eq!(printed(&source), "Rex yips x1\nRex barks x2\nRex makes a sound x3\n");
+}
+
+#[test]
+fn base_needs_an_ancestor_that_has_the_method() {
+    let not_an_ancestor = error(&format!("{}class Cat {{}}\nbase(p, \"speak\", [1], Cat);", PUPPY));
+    assert!(not_an_ancestor.message.contains("not an ancestor"), "{}", not_an_ancestor.message);
+    let missing = error(&format!("{}base(p, \"fetch\", []);", PUPPY));
+    assert!(missing.message.contains("No method fetch"), "{}", missing.message);
+}
+
+const DUCK_AND_ROCK: &str = "class Duck {
+    meth speak(times) {
+        return \"quack x\" + str(times);
+    }
+}
+class Rock {}
+var duck = Duck();
+var rock = Rock();
+";
+
+#[test]
+fn call_if_calls_a_method_that_is_there() {
+    let source = format!("{}print call_if(duck, \"speak\", [2]);\nprint has_method(duck, \"speak\");", DUCK_AND_ROCK);
+    assert_eq!(printed(&source), "quack x2\ntrue\n");
+}
+
+#[test]
+fn call_if_gives_nil_for_a_method_that_is_not() {
+    // a field holding a function isn't a method either
+    let source = format!(
+        "{}print call_if(rock, \"speak\", [2]);\nrock.speak = funct (times) {{ return \"rumble\"; }};\nprint call_if(rock, \"speak\", [1]);\nprint has_method(rock, \"speak\");\nprint has_method(3, \"speak\");",
+        DUCK_AND_ROCK
+    );
+    assert_eq!(printed(&source), "nil\nnil\nfalse\nfalse\n");
+}
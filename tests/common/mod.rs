@@ -0,0 +1,75 @@
+// not every test uses every helper
+#![allow(dead_code)]
+
+use std::{
+    cell::RefCell,
+    io::Write,
+    process::{Command, Stdio},
+    rc::Rc,
+};
+
+use lox::{
+    interpreter::Interpreter,
+    lox::{run_collecting, Diagnostic},
+};
+
+// an in-memory output sink that can still be read after it's handed to an interpreter
+#[derive(Clone, Default)]
+pub struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Output {
+    pub fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// an interpreter writing everything it prints to the returned sink
+pub fn capturing_interpreter() -> (Rc<RefCell<Interpreter>>, Output) {
+    let output = Output::default();
+    let interpreter = Interpreter::with_output(Box::new(output.clone()));
+    (Rc::new(RefCell::new(interpreter)), output)
+}
+
+// what source prints when run, which must succeed
+pub fn printed(source: &str) -> String {
+    let (interpreter, output) = capturing_interpreter();
+    assert_eq!(run_collecting(source, interpreter), Ok(()));
+    output.text()
+}
+
+// the one error running source gives
+pub fn error(source: &str) -> Diagnostic {
+    let (interpreter, _) = capturing_interpreter();
+    let mut errors = run_collecting(source, interpreter).unwrap_err();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    errors.remove(0)
+}
+
+// runs the lox binary with args, piping stdin in, and gives back what it printed
+pub fn lox(args: &[&str], stdin: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Unable to start lox");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use common::printed;
+
+#[test]
+fn return_ends_the_function_there() {
+    let source = "var ran_after = false;
+funct answer(n) {
+    if (n > 0) return 42;
+    ran_after = true;
+    print \"after the return\";
+    return -1;
+}
+print answer(1);
+print ran_after;";
+    assert_eq!(printed(source), "42\nfalse\n");
+}
+
+#[test]
+fn a_bare_return_gives_nil_from_inside_a_loop_too() {
+    let source = "funct first_negative(items) {
+    for (item in items) {
+        if (item < 0) return item;
+        print item;
+    }
+    return;
+}
+print first_negative([3, -2, -5]);
+print first_negative([1]);";
+    assert_eq!(printed(source), "3\n-2\n1\nnil\n");
+}
+
+#[test]
+fn break_in_nested_loops_only_exits_the_innermost_one() {
+    let source = "var outer_runs = 0;
+var inner_runs = 0;
+for (var a = 0; a < 3; a = a + 1) {
+    outer_runs = outer_runs + 1;
+    for (item in [1, 2, 3, 4]) {
+        if (item == 3) break;
+        inner_runs = inner_runs + 1;
+    }
+    print \"outer \" + str(a);
+}
+print outer_runs;
+print inner_runs;";
+    assert_eq!(printed(source), "outer 0\nouter 1\nouter 2\n3\n6\n");
+}
+
+#[test]
+fn break_ends_a_single_loop_early() {
+    let source = "var i = 0;\nwhile (true) {\n    i = i + 1;\n    if (i == 4) break;\n}\nprint i;";
+    assert_eq!(printed(source), "4\n");
+}
+
+#[test]
+fn break_and_continue_reach_only_the_innermost_loop_and_break_skips_its_finally() {
+    let source = "var i = 0;
+var seen = \"\";
+while (i < 6) {
+  i = i + 1;
+  if (i == 2) continue;
+  var j = 0;
+  while (j < 10) {
+    if (j == 1) break;
+    seen = seen + str(i);
+    j = j + 1;
+  }
+  finally seen = seen + \"not added, the inner loop broke\";
+  if (i == 5) break;
+}
+finally
+  print \"not printed, the loop broke\";
+print \"broke after \" + str(i) + \" iterations\";
+print seen;";
+    assert_eq!(printed(source), "broke after 5 iterations\n1345\n");
+}
+
+#[test]
+fn continue_runs_the_rest_of_the_loop_and_its_finally() {
+    let source = "var k = 0;
+while (k < 3) {
+  k = k + 1;
+  continue;
+  print \"never printed\";
+}
+finally print \"finally with k = \" + str(k);";
+    assert_eq!(printed(source), "finally with k = 3\n");
+}
+
+#[test]
+fn a_loops_finally_runs_when_its_condition_ends_it_and_not_on_break() {
+    let source = "funct find(items, wanted) {
+  var i = 0;
+  var result = \"missing\";
+  while (i < len(items)) {
+    if (items[i] == wanted) {
+      result = \"found at \" + str(i);
+      break;
+    }
+    i = i + 1;
+  }
+  finally result = \"searched all \" + str(i);
+  return result;
+}
+print find([1, 2, 3], 2);
+print find([1, 2, 3], 9);
+print find([], 9);";
+    assert_eq!(printed(source), "found at 1\nsearched all 3\nsearched all 0\n");
+}
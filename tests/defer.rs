@@ -0,0 +1,29 @@
+mod common;
+
+use common::printed;
+
+const SAY: &str = "funct say(message) {\n  print message;\n}\n";
+
+#[test]
+fn deferred_expressions_run_last_in_first_out() {
+    let source = format!(
+        "{}{{\n  defer say(\"deferred first, runs last\");\n  defer say(\"deferred second, runs first\");\n  say(\"block body\");\n}}\nsay(\"after the block\");",
+        SAY
+    );
+    assert_eq!(
+        printed(&source),
+        "block body\ndeferred second, runs first\ndeferred first, runs last\nafter the block\n"
+    );
+}
+
+#[test]
+fn deferred_expressions_run_on_an_early_return() {
+    let source = format!(
+        "{}funct early(leave) {{\n  defer say(\"cleanup for \" + str(leave));\n  if (leave) return \"left early\";\n  say(\"ran to the end\");\n  return \"finished\";\n}}\nprint early(true);\nprint early(false);",
+        SAY
+    );
+    assert_eq!(
+        printed(&source),
+        "cleanup for true\nleft early\nran to the end\ncleanup for false\nfinished\n"
+    );
+}
@@ -0,0 +1,174 @@
+mod common;
+
+use std::{cell::RefCell, rc::Rc};
+
+use lox::{
+    interpreter::Interpreter,
+    lox::{run, run_collecting, run_collecting_with, Diagnostic, Phase, RunOptions, Severity},
+};
+
+fn warnings(source: &str) -> Vec<Diagnostic> {
+    run_collecting_with(source, quiet_interpreter(), &RunOptions::default())
+        .expect("source runs")
+        .into_iter()
+        .filter(|d| d.severity == Severity::Warning)
+        .collect()
+}
+
+#[test]
+fn unreachable_print_is_reported_at_its_own_line() {
+    let source = "funct early(x) {\n    return x;\n    print \"after return\";\n}\n";
+    let warnings = warnings(source);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].message, "Code after return is unreachable");
+    assert_eq!(warnings[0].line, 3);
+}
+
+#[test]
+fn unreachable_literal_and_empty_block_are_reported_at_their_own_lines() {
+    let literal = warnings("while (true) {\n  break;\n  1;\n}\n");
+    assert_eq!(literal[0].line, 3);
+    let block = warnings("while (true) {\n  break;\n  {}\n}\n");
+    assert_eq!(block[0].line, 3);
+}
+
+fn quiet_interpreter() -> Rc<RefCell<Interpreter>> {
+    Rc::new(RefCell::new(Interpreter::with_output(Box::new(std::io::sink()))))
+}
+
+#[test]
+fn run_collecting_hands_back_errors_instead_of_printing_them() {
+    assert_eq!(run_collecting("var a = 1;", quiet_interpreter()), Ok(()));
+
+    let errors = run_collecting("print 1;\nprint nope;", quiet_interpreter()).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].phase, Phase::Runtime);
+    assert_eq!(errors[0].line, 2);
+
+    let errors = run_collecting("print (1;", quiet_interpreter()).unwrap_err();
+    assert_eq!(errors[0].phase, Phase::Parse);
+}
+
+#[test]
+fn run_collecting_drops_warnings_that_run_collecting_with_keeps() {
+    let source = "if (true) print 1;";
+    assert_eq!(run_collecting(source, quiet_interpreter()), Ok(()));
+    let warnings = run_collecting_with(source, quiet_interpreter(), &RunOptions::default()).unwrap();
+    assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+fn caret_sits_under_the_start_of_the_offending_token() {
+    let (interpreter, output) = common::capturing_interpreter();
+    assert!(!run("var answer = 1 2345;\n", interpreter, &RunOptions::default()));
+    let text = output.text();
+    let lines: Vec<&str> = text.lines().collect();
+    let snippet = lines.iter().position(|l| l.ends_with("var answer = 1 2345;")).expect("snippet printed");
+    let code_start = lines[snippet].find('|').unwrap() + 2;
+    let caret = lines[snippet + 1];
+    assert_eq!(caret.trim_end().len() - 1, code_start + "var answer = 1 ".len());
+    assert!(caret.ends_with('^'));
+}
+
+#[test]
+fn the_header_reports_the_column_the_caret_is_under() {
+    let (interpreter, output) = common::capturing_interpreter();
+    assert!(!run("var answer = 1 2345;
+", interpreter, &RunOptions::default()));
+    let header = output.text().lines().next().unwrap().to_string();
+    // 2345 starts at column 16, where the caret goes, and ends at 20
+    assert!(header.ends_with("at line 1 column 16"), "{}", header);
+
+    let (interpreter, output) = common::capturing_interpreter();
+    assert!(!run("var x = 1;
+print x + missing;
+", interpreter, &RunOptions::default()));
+    assert!(output.text().starts_with("Attempted to access undefined variable missing. caused by Identifier at 2:11
+"));
+}
+
+fn rendered(source: &str, context: usize) -> String {
+    let (interpreter, output) = common::capturing_interpreter();
+    let options = RunOptions {
+        context: Some(context),
+        ..RunOptions::default()
+    };
+    assert!(!run(source, interpreter, &options));
+    output.text()
+}
+
+#[test]
+fn context_shows_the_lines_around_the_error_in_a_gutter() {
+    let source = (1..=8).map(|n| format!("var v{} = {};\n", n, n)).collect::<String>()
+        + "print v1;\nprint missing;\nprint v2;\nprint v3;\nprint v4;\n";
+    assert_eq!(
+        rendered(&source, 2).lines().collect::<Vec<_>>(),
+        vec![
+            "1",
+            "Attempted to access undefined variable missing. caused by Identifier at 10:7",
+            "   8 | var v8 = 8;",
+            "   9 | print v1;",
+            "> 10 | print missing;",
+            "     |       ^",
+            "  11 | print v2;",
+            "  12 | print v3;",
+        ]
+    );
+}
+
+#[test]
+fn context_stops_at_the_ends_of_the_source() {
+    let text = rendered("print missing;\nprint 1;", 3);
+    let snippet: Vec<&str> = text.lines().skip(1).collect();
+    assert_eq!(snippet, vec!["> 1 | print missing;", "    |       ^", "  2 | print 1;"]);
+    assert!(text.starts_with("Attempted to access undefined variable missing."));
+}
+
+#[test]
+fn too_many_parameters_is_one_error_and_parsing_carries_on_after_it() {
+    let parameters = (0..300).map(|i| format!("p{}", i)).collect::<Vec<_>>().join(", ");
+    let source = format!("funct f({}) {{\n  return p0;\n}}\nprint 1;", parameters);
+    let errors = run_collecting(&source, quiet_interpreter()).unwrap_err();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].phase, Phase::Parse);
+    assert!(errors[0].message.starts_with("Exceeded max parameter count caused by Identifier"));
+}
+
+fn parse_errors(source: &str) -> Vec<String> {
+    let errors = run_collecting(source, quiet_interpreter()).unwrap_err();
+    assert!(errors.iter().all(|e| e.phase == Phase::Parse), "{:?}", errors);
+    errors.into_iter().map(|e| format!("{} at {}:{}", e.message, e.line, e.column)).collect()
+}
+
+#[test]
+fn a_bad_parameter_is_one_error_and_the_function_is_still_skipped_whole() {
+    assert_eq!(
+        parse_errors("funct f(1, +) {}\nprint 1;"),
+        vec!["Expect parameter name caused by Number at 1:10"]
+    );
+    assert_eq!(
+        parse_errors("funct f(, a) {}\nprint 1;"),
+        vec!["Expect parameter name caused by Comma at 1:10"]
+    );
+    assert_eq!(
+        parse_errors("var g = funct (a, , b) { return a; };\nprint 1;"),
+        vec!["Expect parameter name caused by Comma at 1:20"]
+    );
+    assert_eq!(
+        parse_errors("funct f(a b) {\n  return a;\n}\nprint 1;"),
+        vec!["Expect parameters are comma seperated caused by Identifier at 1:12"]
+    );
+}
+
+#[test]
+fn a_parameter_list_missing_its_paren_still_has_its_body_parsed() {
+    assert_eq!(
+        parse_errors("funct f(a, b {\n  return a;\n}\nprint 1;"),
+        vec!["Expect parameters are comma seperated caused by LeftBrace at 1:15"]
+    );
+    // mistakes in the body are reported just as they would be after a good parameter list
+    let body = "{\n  var = 1;\n  return a;\n}\nprint 1;";
+    let mut expected = vec!["Expect parameter name caused by Comma at 1:13".to_string()];
+    expected.extend(parse_errors(&format!("funct f(a, b) {}", body)));
+    assert_eq!(parse_errors(&format!("funct f(a, , b) {}", body)), expected);
+}
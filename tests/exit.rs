@@ -0,0 +1,45 @@
+mod common;
+
+use std::{cell::RefCell, rc::Rc};
+
+use common::capturing_interpreter;
+use lox::{lox::run_collecting, native_functions::ExitHandler};
+
+// keeps the codes it's asked to exit with, stopping the script instead of the process
+struct RecordingExit(Rc<RefCell<Vec<i32>>>);
+
+impl ExitHandler for RecordingExit {
+    fn exit(&mut self, code: i32) -> String {
+        self.0.borrow_mut().push(code);
+        format!("exited with {}", code)
+    }
+}
+
+#[test]
+fn exit_goes_to_the_installed_handler() {
+    let codes = Rc::new(RefCell::new(vec![]));
+    let (interpreter, output) = capturing_interpreter();
+    interpreter
+        .borrow_mut()
+        .set_exit_handler(Box::new(RecordingExit(Rc::clone(&codes))));
+
+    let errors = run_collecting("print \"before\";\nexit(3);\nprint \"after\";", interpreter).unwrap_err();
+
+    assert_eq!(*codes.borrow(), vec![3]);
+    assert_eq!(output.text(), "before\n");
+    assert!(errors[0].message.starts_with("exited with 3"));
+    assert_eq!(errors[0].line, 2);
+}
+
+#[test]
+fn invalid_codes_never_reach_the_handler() {
+    let codes = Rc::new(RefCell::new(vec![]));
+    let (interpreter, _) = capturing_interpreter();
+    interpreter
+        .borrow_mut()
+        .set_exit_handler(Box::new(RecordingExit(Rc::clone(&codes))));
+
+    assert!(run_collecting("exit(1.5);", interpreter.clone()).is_err());
+    assert!(run_collecting("exit(10000000000);", interpreter).is_err());
+    assert!(codes.borrow().is_empty());
+}
@@ -0,0 +1,58 @@
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use lox::{interpreter::Interpreter, lox::run_collecting};
+
+// a sink that holds writes back until it's flushed, recording what each flush let out
+#[derive(Clone, Default)]
+struct Flushes {
+    pending: Rc<RefCell<Vec<u8>>>,
+    flushed: Rc<RefCell<Vec<String>>>,
+}
+
+impl Flushes {
+    fn flushed(&self) -> Vec<String> {
+        self.flushed.borrow().clone()
+    }
+}
+
+impl Write for Flushes {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let pending = self.pending.replace(vec![]);
+        if !pending.is_empty() {
+            self.flushed.borrow_mut().push(String::from_utf8(pending).unwrap());
+        }
+        Ok(())
+    }
+}
+
+fn interpreter(flushes: &Flushes) -> Rc<RefCell<Interpreter>> {
+    Rc::new(RefCell::new(Interpreter::with_output(Box::new(flushes.clone()))))
+}
+
+#[test]
+fn flush_lets_out_everything_printed_so_far() {
+    let flushes = Flushes::default();
+    let source = "print \"a\";\nprint \"b\";\nflush();\nprint \"c\";";
+
+    assert_eq!(run_collecting(source, interpreter(&flushes)), Ok(()));
+
+    assert_eq!(flushes.flushed(), vec!["a\nb\n"]);
+    assert_eq!(*flushes.pending.borrow(), b"c\n");
+}
+
+#[test]
+fn flush_on_print_lets_out_each_line_in_order() {
+    let flushes = Flushes::default();
+    let interpreter = interpreter(&flushes);
+    interpreter.borrow_mut().set_flush_on_print(true);
+    let source = "for (var i = 0; i < 3; i = i + 1) print i;\nflush();\nprint \"done\";";
+
+    assert_eq!(run_collecting(source, interpreter), Ok(()));
+
+    assert_eq!(flushes.flushed(), vec!["0\n", "1\n", "2\n", "done\n"]);
+    assert!(flushes.pending.borrow().is_empty());
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::capturing_interpreter;
+use lox::{heap_stats::LiveCounts, lox::run_collecting};
+
+// runs source with heap stats on and gives back what's still alive at the end
+fn live_after(source: &str) -> LiveCounts {
+    let (interpreter, _) = capturing_interpreter();
+    interpreter.borrow_mut().enable_heap_stats();
+    assert_eq!(run_collecting(source, interpreter.clone()), Ok(()));
+    let live = interpreter.borrow_mut().finish_heap_stats();
+    live.expect("heap stats were enabled")
+}
+
+const NOTHING: LiveCounts = LiveCounts {
+    lists: 0,
+    maps: 0,
+    instances: 0,
+    closures: 0,
+};
+
+#[test]
+fn arrays_without_cycles_are_all_freed() {
+    let source = "
+        var kept = [];
+        for (var i = 0; i < 100; i = i + 1) {
+            var pair = [i, {\"at\": i}];
+            push(kept, pair);
+        }
+        assert len(kept) == 100;
+    ";
+    assert_eq!(live_after(source), NOTHING);
+}
+
+#[test]
+fn a_list_that_contains_itself_stays_alive() {
+    let live = live_after("{ var a = []; push(a, a); }\n{ var b = [1, 2]; }");
+    assert_eq!(live, LiveCounts { lists: 1, ..NOTHING });
+}
+
+#[test]
+fn an_instance_and_the_closure_it_holds_stay_alive() {
+    let source = "class Node {}\n{\n  var b = Node();\n  b.callback = funct () { return b; };\n}";
+    let live = live_after(source);
+    assert_eq!(live.instances, 1);
+    assert_eq!(live.closures, 1);
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use std::io::Cursor;
+
+use common::capturing_interpreter;
+use lox::lox::run_collecting;
+
+#[test]
+fn input_reads_lines_from_set_input_until_eof() {
+    let (interpreter, out) = capturing_interpreter();
+    interpreter
+        .borrow_mut()
+        .set_input(Box::new(Cursor::new("first\r\n  second  \nlast")));
+    let source = "print input();\nprint input(\"> \");\nprint input();\nprint input();";
+    assert_eq!(run_collecting(source, interpreter), Ok(()));
+    // only the line ending is trimmed, and the prompt is written before the line is read
+    assert_eq!(out.text(), "first\n>   second  \nlast\nnil\n");
+}
+
+#[test]
+fn read_all_gives_back_the_rest_of_the_input_with_its_newlines() {
+    let (interpreter, out) = capturing_interpreter();
+    interpreter
+        .borrow_mut()
+        .set_input(Box::new(Cursor::new("skipped\none\ntwo\n\nthree\n")));
+    let source = "input();\nvar rest = read_all();\nprint len(rest);\nprint rest;\nprint read_all() == \"\";";
+    assert_eq!(run_collecting(source, interpreter), Ok(()));
+    assert_eq!(out.text(), "15\none\ntwo\n\nthree\n\ntrue\n");
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use std::{cell::Cell, rc::Rc};
+
+use common::capturing_interpreter;
+use lox::{common::LoxType, lox::run_collecting, native_functions::HostFunction};
+
+#[test]
+fn a_thunk_runs_its_function_once_however_often_it_is_forced() {
+    let runs = Rc::new(Cell::new(0));
+    let counted = Rc::clone(&runs);
+    let (interpreter, output) = capturing_interpreter();
+    interpreter.borrow_mut().register_native(
+        "expensive",
+        Rc::new(HostFunction::new("expensive", 0, move |_| {
+            counted.set(counted.get() + 1);
+            Ok(LoxType::Number(42.0))
+        })),
+    );
+
+    let source = "var thunk = lazy(expensive);\nprint force(thunk);\nprint force(thunk);\nprint force(thunk) + 1;";
+    assert_eq!(run_collecting(source, Rc::clone(&interpreter)), Ok(()));
+
+    assert_eq!(output.text(), "42\n42\n43\n");
+    assert_eq!(runs.get(), 1);
+}
+
+#[test]
+fn a_thunk_is_not_run_until_it_is_forced() {
+    let (interpreter, _) = capturing_interpreter();
+    let source = "var runs = 0;\nvar thunk = lazy(funct () { runs = runs + 1; return runs; });";
+    assert_eq!(run_collecting(source, Rc::clone(&interpreter)), Ok(()));
+    assert_eq!(interpreter.borrow().get_global("runs"), Some(LoxType::Number(0.0)));
+
+    assert_eq!(run_collecting("force(thunk);\nforce(thunk);", Rc::clone(&interpreter)), Ok(()));
+    assert_eq!(interpreter.borrow().get_global("runs"), Some(LoxType::Number(1.0)));
+}
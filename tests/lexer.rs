@@ -0,0 +1,55 @@
+mod common;
+
+use common::capturing_interpreter;
+use lox::lox::{run_collecting, Diagnostic, Phase};
+
+fn lex_errors(source: &str) -> Vec<Diagnostic> {
+    let (interpreter, _) = capturing_interpreter();
+    let errors = run_collecting(source, interpreter).unwrap_err();
+    assert!(errors.iter().all(|e| e.phase == Phase::Lex), "{:?}", errors);
+    errors
+}
+
+#[test]
+fn an_exponent_without_digits_is_rejected() {
+    let errors = lex_errors("print 1e;");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "invalid numeric literal 1e. invalid symbol e");
+
+    let errors = lex_errors("print 2E+;");
+    assert_eq!(errors[0].message, "invalid numeric literal 2E+. invalid symbol E");
+}
+
+#[test]
+fn an_exponent_with_digits_is_accepted() {
+    let (interpreter, output) = capturing_interpreter();
+    assert_eq!(run_collecting("print 1e3;\nprint 2E+1;\nprint 5e-1;", interpreter), Ok(()));
+    assert_eq!(output.text(), "1000\n20\n0.5\n");
+}
+
+#[test]
+fn a_separator_must_sit_between_two_digits() {
+    for (source, literal) in [
+        ("print 1._5;", "1."),
+        ("print 1__0;", "1_"),
+        ("print 1_;", "1_"),
+        ("print 1_.5;", "1_"),
+        ("print 1e_5;", "1e"),
+    ] {
+        let errors = lex_errors(source);
+        assert_eq!(errors.len(), 1, "{}", source);
+        assert_eq!(
+            errors[0].message,
+            format!("invalid numeric literal {}. invalid symbol _", literal),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn separators_between_digits_are_stripped() {
+    let (interpreter, output) = capturing_interpreter();
+    assert_eq!(run_collecting("print 1_000;\nprint 3.141_5;\nprint 1e1_0;", interpreter), Ok(()));
+    assert_eq!(output.text(), "1000\n3.1415\n10000000000\n");
+}
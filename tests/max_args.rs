@@ -0,0 +1,60 @@
+mod common;
+
+use common::capturing_interpreter;
+use lox::lox::{run_collecting, run_collecting_with, RunOptions};
+
+fn names(prefix: &str, count: usize) -> String {
+    (0..count).map(|i| format!("{}{}", prefix, i)).collect::<Vec<_>>().join(", ")
+}
+
+#[test]
+fn a_function_can_take_exactly_255_parameters_and_arguments() {
+    let source = format!(
+        "funct f({}) {{ return p254; }}\nprint f({});",
+        names("p", 255),
+        vec!["1"; 255].join(", ")
+    );
+    let (interpreter, out) = capturing_interpreter();
+    assert_eq!(run_collecting(&source, interpreter), Ok(()));
+    assert_eq!(out.text(), "1\n");
+}
+
+#[test]
+fn the_256th_parameter_or_argument_is_an_error() {
+    let (interpreter, _) = capturing_interpreter();
+    let errors = run_collecting(&format!("funct f({}) {{}}", names("p", 256)), interpreter).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.starts_with("Exceeded max parameter count"), "{}", errors[0].message);
+
+    let (interpreter, _) = capturing_interpreter();
+    let errors = run_collecting(&format!("coalesce({});", vec!["1"; 256].join(", ")), interpreter).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.starts_with("Exceeded max argument count"), "{}", errors[0].message);
+}
+
+const SPREAD_300: &str = "var l = [];\nvar i = 0;\nwhile (i < 300) { push(l, i + 1); i = i + 1; }\nprint coalesce(...l);\nprint format(\"{}\", ...l);";
+
+#[test]
+fn variadic_natives_keep_to_the_default_limit_at_runtime() {
+    let (interpreter, _) = capturing_interpreter();
+    let errors = run_collecting(SPREAD_300, interpreter).unwrap_err();
+    assert!(errors[0].message.starts_with("Expected 1 to 255 arguments, found 300"), "{}", errors[0].message);
+}
+
+#[test]
+fn variadic_natives_take_any_number_of_arguments_with_no_limit() {
+    let options = RunOptions { max_args: None, ..RunOptions::default() };
+    let (interpreter, out) = capturing_interpreter();
+    interpreter.borrow_mut().set_max_args(None);
+    let errors = run_collecting_with(SPREAD_300, interpreter, &options).unwrap_err();
+    assert_eq!(out.text(), "1\n");
+    assert!(
+        errors[0].message.starts_with("format template has 1 placeholders but 300 values were given"),
+        "{}",
+        errors[0].message
+    );
+
+    let out = common::lox(&["--max-args", "0"], "var l = [];\nvar i = 0;\nwhile (i < 300) { push(l, i + 1); i = i + 1; }\nprint coalesce(...l);\n");
+    assert!(out.contains("1\n"), "{}", out);
+    assert!(!out.contains("Expected"), "{}", out);
+}
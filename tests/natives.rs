@@ -0,0 +1,71 @@
+mod common;
+
+use common::{error, printed};
+
+#[test]
+fn try_call_gives_back_the_value_of_a_call_that_succeeds() {
+    let source = "var result = try_call(funct (a, b) { return a / b; }, [6, 3]);\nprint result[\"ok\"];\nprint result[\"value\"];";
+    assert_eq!(printed(source), "true\n2\n");
+}
+
+#[test]
+fn try_call_gives_back_the_error_of_a_call_that_raises() {
+    let source = "var result = try_call(funct (a, b) { return a / b; }, [1, 0]);\nprint result[\"ok\"];\nprint result[\"error\"];\nprint len(result);";
+    assert_eq!(printed(source), "false\ncannot divide by 0 in 1.0 / 0.0\n2\n");
+}
+
+#[test]
+fn try_call_needs_a_function_and_a_list() {
+    assert!(error("try_call(1, []);").message.starts_with("try_call expects a function"));
+    assert!(error("try_call(clock, 1);").message.starts_with("try_call expects a list"));
+}
+
+#[test]
+fn codes_and_string_from_codes_are_inverses() {
+    let source = "print codes(\"hi\") == [104, 105];\nprint string_from_codes([104, 105]) == \"hi\";\nprint string_from_codes(codes(\"\"));\nprint codes(\"é\");";
+    assert_eq!(printed(source), "true\ntrue\n\n[233]\n");
+}
+
+#[test]
+fn string_from_codes_rejects_what_isnt_a_code_point() {
+    for code in ["104.5", "-1", "55296", "1114112"] {
+        let message = error(&format!("string_from_codes([{}]);", code)).message;
+        assert!(message.contains("which isn't a code point"), "{}", message);
+    }
+    assert!(error("string_from_codes([\"h\"]);").message.starts_with("string_from_codes expects a number"));
+}
+
+#[test]
+fn max_by_and_min_by_pick_by_key() {
+    let source = "var strings = [\"ab\", \"abcd\", \"a\", \"wxyz\"];\nprint max_by(strings, len);\nprint min_by(strings, len);\nprint min_by([3, -5, 4], funct (n) { return n * n; });";
+    assert_eq!(printed(source), "abcd\na\n3\n");
+}
+
+#[test]
+fn max_by_needs_a_non_empty_list_and_numeric_keys() {
+    assert!(error("max_by([], len);").message.starts_with("max_by of an empty list"));
+    assert!(error("min_by([1, 2], str);").message.starts_with("min_by expects the key function to return a number"));
+    assert!(error("max_by([1], 2);").message.starts_with("max_by expects a key function"));
+}
+
+const FLAKY: &str = "var attempts = 0;
+funct flaky() {
+    attempts = attempts + 1;
+    if (attempts < 3) return [][attempts];
+    return \"worked after \" + str(attempts);
+}
+";
+
+#[test]
+fn retry_gives_back_the_first_success() {
+    let source = format!("{}print retry(flaky, 5);\nprint attempts;", FLAKY);
+    assert_eq!(printed(&source), "worked after 3\n3\n");
+}
+
+#[test]
+fn retry_raises_the_last_error_once_out_of_attempts() {
+    // each attempt fails with its own index, so the message shows which one was raised
+    let diagnostic = error(&format!("{}retry(flaky, 2);", FLAKY));
+    assert!(diagnostic.message.starts_with("Index 2 out of bounds"), "{}", diagnostic.message);
+    assert!(error("retry(funct () { return 1; }, 0);").message.contains("at least 1"));
+}
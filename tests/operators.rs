@@ -0,0 +1,29 @@
+mod common;
+
+use common::{error, printed};
+use lox::lox::Phase;
+
+#[test]
+fn ordering_needs_two_numbers_or_two_strings() {
+    for (source, operator) in [("true < 1;", "<"), ("nil <= \"x\";", "<="), ("[1] > [0];", ">")] {
+        let diagnostic = error(source);
+        assert_eq!(diagnostic.phase, Phase::Runtime);
+        assert!(diagnostic.message.starts_with("invalid operands"), "{}", diagnostic.message);
+        assert!(diagnostic.message.contains(&format!("for {}", operator)), "{}", diagnostic.message);
+    }
+    assert_eq!(printed("print 1 < 2;\nprint \"a\" <= \"b\";"), "true\ntrue\n");
+}
+
+#[test]
+fn equality_between_mixed_types_is_allowed() {
+    assert_eq!(printed("print true == 1;\nprint nil != \"x\";"), "false\ntrue\n");
+}
+
+#[test]
+fn whole_numbers_print_without_a_fraction() {
+    let source = "print 3;\nprint 3.5;\nprint -0;\nprint 1000000;\nprint 4.0;\nprint 6 / 2;\nprint 10 / 4;\nprint 1 / 3;\nprint str(2.50);\nprint [1.0, 2.25];\nprint 602200000000000000000000;";
+    assert_eq!(
+        printed(source),
+        "3\n3.5\n-0\n1000000\n4\n3\n2.5\n0.3333333333333333\n2.5\n[1, 2.25]\n602200000000000000000000\n"
+    );
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::capturing_interpreter;
+use lox::lox::run_collecting;
+
+fn printed_with(source: &str, configure: impl FnOnce(&mut lox::interpreter::Interpreter)) -> String {
+    let (interpreter, output) = capturing_interpreter();
+    configure(&mut interpreter.borrow_mut());
+    assert_eq!(run_collecting(source, interpreter), Ok(()));
+    output.text()
+}
+
+const CHANGES_ITS_PARAMETER: &str =
+    "funct change(l) { l[0] = 99; push(l, 4); }\nvar list = [1, 2, 3];\nchange(list);\nprint list;\nvar alias = list;\nalias[1] = 0;\nprint list;";
+
+#[test]
+fn lists_passed_to_functions_are_shared_by_default() {
+    assert_eq!(printed_with(CHANGES_ITS_PARAMETER, |_| {}), "[99, 2, 3, 4]\n[99, 0, 3, 4]\n");
+}
+
+#[test]
+fn value_semantics_gives_functions_and_variables_their_own_copy() {
+    let out = printed_with(CHANGES_ITS_PARAMETER, |interpreter| interpreter.set_value_semantics(true));
+    assert_eq!(out, "[1, 2, 3]\n[1, 2, 3]\n");
+}
+
+const POINT_THREE: &str = "print 0.1 + 0.2 == 0.3;\nprint 0.1 + 0.2 != 0.3;\nprint 1 == 1.001;";
+
+#[test]
+fn numbers_are_compared_exactly_by_default() {
+    assert_eq!(printed_with(POINT_THREE, |_| {}), "false\ntrue\nfalse\n");
+}
+
+#[test]
+fn float_epsilon_makes_close_numbers_equal() {
+    let out = printed_with(POINT_THREE, |interpreter| interpreter.set_float_epsilon(Some(1e-9)));
+    // numbers further apart than the epsilon are still unequal
+    assert_eq!(out, "true\nfalse\nfalse\n");
+}
+
+const REDEFINES_BUILTINS: &str = "var clock = 5;\nprint clock;\nlen = \"not a function\";\nprint len;";
+
+#[test]
+fn builtins_can_be_redefined_by_default() {
+    assert_eq!(printed_with(REDEFINES_BUILTINS, |_| {}), "5\nnot a function\n");
+}
+
+#[test]
+fn protect_builtins_makes_redefining_or_assigning_them_an_error() {
+    let (interpreter, output) = capturing_interpreter();
+    interpreter.borrow_mut().set_protect_builtins(true);
+    let errors = run_collecting(REDEFINES_BUILTINS, interpreter).unwrap_err();
+    let messages: Vec<(&str, u32)> = errors.iter().map(|e| (e.message.as_str(), e.line)).collect();
+    assert_eq!(
+        messages,
+        vec![
+            ("Can't redefine a built-in function caused by clock", 1),
+            ("Can't redefine a built-in function caused by len", 3),
+        ]
+    );
+    assert_eq!(output.text(), "");
+
+    // locals can still share a built-in's name
+    let shadowed = "{\n  var str = \"shadowed\";\n  print str;\n}";
+    assert_eq!(printed_with(shadowed, |interpreter| interpreter.set_protect_builtins(true)), "shadowed\n");
+}
+
+#[test]
+fn the_protect_builtins_flag_turns_protection_on() {
+    let out = common::lox(&[], "var clock = 5;\nprint clock;\n");
+    assert!(out.contains("5\n") && !out.contains("built-in"), "{}", out);
+    let out = common::lox(&["--protect-builtins"], "var clock = 5;\n");
+    assert!(out.contains("Can't redefine a built-in function caused by clock"), "{}", out);
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::{capturing_interpreter, error};
+use lox::{
+    common::LoxType,
+    lox::{run_collecting, Phase},
+};
+
+fn remembered_results(interpreter: &lox::interpreter::Interpreter, name: &str) -> usize {
+    match interpreter.get_global(name) {
+        Some(LoxType::Function(function)) => function.remembered_results(),
+        other => panic!("{} isn't a function: {:?}", name, other),
+    }
+}
+
+#[test]
+fn a_pure_function_runs_once_for_each_distinct_argument() {
+    let (interpreter, output) = capturing_interpreter();
+    let source = "pure funct fib(n) {\n  if (n < 2) return n;\n  return fib(n - 1) + fib(n - 2);\n}\nprint fib(30);";
+    assert_eq!(run_collecting(source, Rc::clone(&interpreter)), Ok(()));
+    assert_eq!(output.text(), "832040\n");
+    // fib(0) to fib(30), each worked out once however many times the recursion asks for it
+    assert_eq!(remembered_results(&interpreter.borrow(), "fib"), 31);
+
+    assert_eq!(run_collecting("print fib(30);\nprint fib(31);", Rc::clone(&interpreter)), Ok(()));
+    assert_eq!(remembered_results(&interpreter.borrow(), "fib"), 32);
+}
+
+#[test]
+fn only_pure_functions_remember_results() {
+    let (interpreter, _) = capturing_interpreter();
+    let source = "funct double(n) { return n * 2; }\npure funct twice(n) { return n * 2; }\npure funct first(l) { return l[0]; }\ndouble(1);\ntwice(1);\ntwice(1);\nfirst([1]);";
+    assert_eq!(run_collecting(source, Rc::clone(&interpreter)), Ok(()));
+    assert_eq!(remembered_results(&interpreter.borrow(), "double"), 0);
+    assert_eq!(remembered_results(&interpreter.borrow(), "twice"), 1);
+    // lists can change after the call, so calls taking them aren't remembered
+    assert_eq!(remembered_results(&interpreter.borrow(), "first"), 0);
+}
+
+#[test]
+fn a_pure_function_cant_use_outside_state() {
+    for source in [
+        "var calls = 0;\npure funct counted(n) {\n  calls = calls + 1;\n  return n;\n}",
+        "var offset = 10;\npure funct shifted(n) {\n  return n + offset;\n}",
+        "funct impure() { return 1; }\npure funct calls_impure() {\n  return impure();\n}",
+        "// natives count as outside state\npure funct timed() {\n  return clock();\n}",
+    ] {
+        let diagnostic = error(source);
+        assert_eq!(diagnostic.phase, Phase::Resolve, "{}", source);
+        assert!(
+            diagnostic.message.starts_with("A pure function can only use its own variables and other pure functions"),
+            "{}",
+            diagnostic.message
+        );
+        assert_eq!(diagnostic.line, 3, "{}", source);
+    }
+}
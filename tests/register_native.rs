@@ -0,0 +1,46 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::capturing_interpreter;
+use lox::{common::LoxType, lox::run_collecting, native_functions::HostFunction};
+
+fn double() -> HostFunction {
+    HostFunction::new("double", 1, |args| match &args[0] {
+        LoxType::Number(n) => Ok(LoxType::Number(n * 2.0)),
+        other => Err(format!("double expects a number, found {}", other)),
+    })
+}
+
+#[test]
+fn scripts_call_a_registered_native() {
+    let (interpreter, output) = capturing_interpreter();
+    interpreter.borrow_mut().register_native("double", Rc::new(double()));
+
+    let result = run_collecting("print double(21);\nprint double(double(1.5));", interpreter);
+
+    assert_eq!(result, Ok(()));
+    assert_eq!(output.text(), "42\n6\n");
+}
+
+#[test]
+fn a_registered_native_errors_at_the_call() {
+    let (interpreter, _) = capturing_interpreter();
+    interpreter.borrow_mut().register_native("double", Rc::new(double()));
+
+    let errors = run_collecting("var a = 1;\nprint double(\"two\");", interpreter).unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.contains("double expects a number, found two"));
+    assert_eq!(errors[0].line, 2);
+}
+
+#[test]
+fn a_registered_native_counts_as_a_builtin() {
+    let (interpreter, _) = capturing_interpreter();
+    interpreter.borrow_mut().register_native("double", Rc::new(double()));
+    interpreter.borrow_mut().set_protect_builtins(true);
+
+    assert!(interpreter.borrow().is_builtin("double"));
+    assert!(run_collecting("var double = 1;", interpreter).is_err());
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use common::lox;
+
+#[test]
+fn input_leaves_later_lines_for_the_repl() {
+    let out = lox(&[], "var x = input();\nhello\nprint x;\nprint 1+2;\n");
+    assert_eq!(out, ":> :> hello\n:> 3\n:> ");
+}
+
+#[test]
+fn env_lists_globals_through_the_interpreter_output() {
+    let out = lox(&[], "var b = 2;\nvar a = \"one\";\n:env\n");
+    assert_eq!(out, ":> :> :> a = one\nb = 2\n:> ");
+}
+
+#[test]
+fn quit_leaves_a_half_typed_statement() {
+    let out = lox(&[], "var a = 1;\nfunct f() {\n:env\n:quit\nprint 1;\n");
+    assert_eq!(out, ":> :> .. a = 1\n.. ");
+}
+
+#[test]
+fn reset_drops_a_half_typed_statement_and_the_globals() {
+    let out = lox(&[], "var a = 1;\nfunct f() {\n:reset\n:env\nprint 2;\n");
+    assert_eq!(out, ":> :> .. :> :> 2\n:> ");
+}
+
+#[test]
+fn the_repl_after_files_sees_what_they_defined() {
+    let out = lox(
+        &["-f", "test_files/interactive/setup.lox", "-i"],
+        "print greeting;\nprint twice(21);\n:env\n",
+    );
+    assert_eq!(out, ":> hi\n:> 42\n:> greeting = hi\ntwice = function <1>\n:> ");
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::printed;
+
+#[test]
+fn a_method_body_resolves_outer_locals_parameters_and_this() {
+    // same-named globals exist, so a method resolving to the wrong scope would print them
+    let source = "var greeting = \"global greeting\";
+var name = \"global name\";
+{
+    var greeting = \"local greeting\";
+    class Greeter {
+        meth greet(name) {
+            print greeting + \", \" + name + \" from \" + this.place;
+        }
+    }
+    var greeter = Greeter();
+    greeter.place = \"the block\";
+    greeter.greet(\"param name\");
+    greeting = \"changed local greeting\";
+    greeter.greet(\"another name\");
+}
+print greeting;
+print name;";
+    assert_eq!(
+        printed(source),
+        "local greeting, param name from the block\n\
+         changed local greeting, another name from the block\n\
+         global greeting\n\
+         global name\n"
+    );
+}
+
+#[test]
+fn the_index_in_a_subscript_resolves_to_the_local() {
+    let source = "var i = 0;
+var key = \"global\";
+var items = [\"zero\", \"one\", \"two\"];
+{
+    var i = 2;
+    var key = \"local\";
+    print items[i];
+    items[i] = \"changed two\";
+    print items;
+    funct pick() {
+        return [items[i], {key: i}];
+    }
+    i = 1;
+    print pick();
+}
+print items[i];";
+    assert_eq!(
+        printed(source),
+        "two\n[zero, one, changed two]\n[one, {local: 1}]\nzero\n"
+    );
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use common::lox;
+
+const PRELUDE: &str = "test_files/multi/prelude.lox";
+const SCRIPT: &str = "test_files/multi/script.lox";
+const FAILS: &str = "test_files/multi/fails.lox";
+
+const UNDEFINED: &str = "Attempted to access undefined variable undefined_variable.";
+
+#[test]
+fn later_files_see_the_globals_of_earlier_ones() {
+    assert_eq!(lox(&["-f", PRELUDE, "-f", SCRIPT], ""), "hello world\n1\n");
+}
+
+#[test]
+fn files_stop_at_the_first_failure() {
+    let out = lox(&["-f", PRELUDE, "-f", FAILS, "-f", SCRIPT], "");
+    assert!(out.starts_with(UNDEFINED), "{}", out);
+    assert!(!out.contains("hello world"), "{}", out);
+}
+
+#[test]
+fn keep_going_runs_the_files_after_a_failure() {
+    let out = lox(&["--keep-going", "-f", PRELUDE, "-f", FAILS, "-f", SCRIPT], "");
+    assert!(out.starts_with(UNDEFINED), "{}", out);
+    assert!(out.ends_with("hello world\n1\n"), "{}", out);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use common::capturing_interpreter;
+use lox::{lox::run_collecting, native_functions::Sleeper};
+
+// keeps the durations it's asked to sleep for without blocking
+struct RecordingSleeper(Rc<RefCell<Vec<Duration>>>);
+
+impl Sleeper for RecordingSleeper {
+    fn sleep(&mut self, duration: Duration) {
+        self.0.borrow_mut().push(duration);
+    }
+}
+
+#[test]
+fn sleep_forwards_the_requested_duration() {
+    let slept = Rc::new(RefCell::new(vec![]));
+    let (interpreter, output) = capturing_interpreter();
+    interpreter
+        .borrow_mut()
+        .set_sleeper(Box::new(RecordingSleeper(Rc::clone(&slept))));
+
+    let started = std::time::Instant::now();
+    let result = run_collecting("sleep(250);\nsleep(0.5);\nprint sleep(60000);", interpreter);
+
+    assert_eq!(result, Ok(()));
+    assert_eq!(
+        *slept.borrow(),
+        vec![
+            Duration::from_millis(250),
+            Duration::from_micros(500),
+            Duration::from_secs(60)
+        ]
+    );
+    assert_eq!(output.text(), "nil\n");
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn invalid_durations_never_reach_the_sleeper() {
+    let slept = Rc::new(RefCell::new(vec![]));
+    let (interpreter, _) = capturing_interpreter();
+    interpreter
+        .borrow_mut()
+        .set_sleeper(Box::new(RecordingSleeper(Rc::clone(&slept))));
+
+    assert!(run_collecting("sleep(-1);", interpreter.clone()).is_err());
+    assert!(run_collecting("sleep(\"soon\");", interpreter).is_err());
+    assert!(slept.borrow().is_empty());
+}
+
+#[test]
+fn clock_moves_on_by_a_fraction_of_a_second_around_a_short_sleep() {
+    let (interpreter, _) = capturing_interpreter();
+    let source = "var start = clock();\nsleep(20);\nvar elapsed = clock() - start;";
+    assert_eq!(run_collecting(source, Rc::clone(&interpreter)), Ok(()));
+
+    // clock is in seconds, so 20ms is 0.02 of it and not 20
+    let elapsed = match interpreter.borrow().get_global("elapsed") {
+        Some(lox::common::LoxType::Number(n)) => n,
+        other => panic!("elapsed isn't a number: {:?}", other),
+    };
+    assert!((0.02..1.0).contains(&elapsed), "clock moved on by {}", elapsed);
+}
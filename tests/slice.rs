@@ -0,0 +1,22 @@
+mod common;
+
+use common::printed;
+
+#[test]
+fn a_slice_reads_the_elements_between_its_bounds() {
+    let source = "var arr = [0, 1, 2, 3, 4];\nprint arr[1:3];\nprint arr[:2];\nprint arr[3:];\nprint arr[2:2];\nprint \"hello\"[1:4];";
+    assert_eq!(printed(source), "[1, 2]\n[0, 1]\n[3, 4]\n[]\nell\n");
+}
+
+#[test]
+fn splicing_a_slice_can_change_the_lists_length() {
+    let source = "var arr = [0, 1, 2, 3, 4];
+arr[1:3] = [\"a\", \"b\", \"c\", \"d\"];
+print arr;
+print len(arr);
+arr[0:5] = [];
+print arr;
+arr[:0] = [9];
+print arr;";
+    assert_eq!(printed(source), "[0, a, b, c, d, 3, 4]\n7\n[3, 4]\n[9, 3, 4]\n");
+}
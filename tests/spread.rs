@@ -0,0 +1,31 @@
+mod common;
+
+use common::{error, printed};
+
+#[test]
+fn a_list_spreads_into_call_arguments() {
+    let source = "funct add3(a, b, c) { return a + b + c; }\nprint add3(...[1, 2, 3]);\nprint add3(1, ...[2], 3);";
+    assert_eq!(printed(source), "6\n6\n");
+}
+
+#[test]
+fn a_list_spreads_into_a_list_literal() {
+    assert_eq!(printed("print [...([1, 2]), 3];\nprint [...[], 0, ...[1]];"), "[1, 2, 3]\n[0, 1]\n");
+}
+
+#[test]
+fn the_spread_list_is_copied_not_aliased() {
+    assert_eq!(printed("var a = [1, 2];\nvar b = [...a];\nb[0] = 9;\nprint a;\nprint b;"), "[1, 2]\n[9, 2]\n");
+}
+
+#[test]
+fn arity_is_checked_after_spreading() {
+    let message = error("funct add3(a, b, c) { return a + b + c; }\nadd3(...[1, 2]);").message;
+    assert!(message.starts_with("Expected 3 arguments, found 2"), "{}", message);
+}
+
+#[test]
+fn only_lists_can_be_spread() {
+    let message = error("print [...1];").message;
+    assert!(message.starts_with("Can only spread a list, found 1"), "{}", message);
+}